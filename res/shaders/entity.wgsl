@@ -0,0 +1,45 @@
+struct Draw {
+    view_proj: mat4x4<f32>,
+    tex_index: u32,
+    textured: u32,
+}
+
+@group(0) @binding(0) var textures: binding_array<texture_2d<f32>>;
+@group(0) @binding(1) var bilinear: sampler;
+
+@group(1) @binding(0) var<uniform> draw: Draw;
+@group(1) @binding(1) var<storage, read> instances: array<mat4x4<f32>>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(instance_index) instance: u32,
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VertexOutput {
+    let model = instances[instance];
+
+    var out: VertexOutput;
+    out.position = draw.view_proj * model * vec4<f32>(pos, 1.0);
+    out.normal = normalize((model * vec4<f32>(normal, 0.0)).xyz);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = vec4<f32>(1.0);
+    if draw.textured != 0u {
+        color = textureSample(textures[draw.tex_index], bilinear, in.uv);
+    }
+
+    // Fixed key light so untextured meshes still show their shape
+    let light = max(dot(in.normal, normalize(vec3<f32>(0.4, 1.0, 0.6))), 0.0) * 0.7 + 0.3;
+    return vec4<f32>(color.rgb * light, color.a);
+}
@@ -0,0 +1,261 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use winit::event::VirtualKeyCode;
+
+use super::resource::{readback::TexReadback, texture::TextureData};
+
+pub enum RecordingFormat {
+    Png,
+    Y4m,
+}
+
+struct Recording {
+    format: RecordingFormat,
+    directory: PathBuf,
+    fps: u32,
+    frame: u32,
+    y4m: Option<BufWriter<File>>,
+}
+
+pub struct FrameCapture {
+    pub directory: PathBuf,
+    // `None` disables the hotkey
+    pub screenshot_key: Option<VirtualKeyCode>,
+    pub record_key: Option<VirtualKeyCode>,
+    screenshot_requested: bool,
+    recording: Option<Recording>,
+}
+
+impl FrameCapture {
+    pub fn new(directory: &str) -> FrameCapture {
+        Self {
+            directory: PathBuf::from(directory),
+            screenshot_key: Some(VirtualKeyCode::F12),
+            record_key: Some(VirtualKeyCode::F11),
+            screenshot_requested: false,
+            recording: None,
+        }
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn start_recording(&mut self, format: RecordingFormat, fps: u32) -> io::Result<()> {
+        let directory = self
+            .directory
+            .join(format!("recording_{}", Self::timestamp()));
+        std::fs::create_dir_all(&directory)?;
+
+        self.recording = Some(Recording {
+            format,
+            directory,
+            fps,
+            frame: 0,
+            y4m: None,
+        });
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recording.take() {
+            Some(Recording {
+                y4m: Some(mut writer),
+                ..
+            }) => writer.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn toggle_recording(&mut self, format: RecordingFormat, fps: u32) -> io::Result<()> {
+        if self.is_recording() {
+            self.stop_recording()
+        } else {
+            self.start_recording(format, fps)
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn fixed_dt(&self) -> Option<f32> {
+        self.recording
+            .as_ref()
+            .map(|recording| 1.0 / recording.fps as f32)
+    }
+
+    pub fn wants_frame(&self) -> bool {
+        self.screenshot_requested || self.is_recording()
+    }
+
+    pub fn save_png(&self, name: &str, image: &TextureData) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("{name}.png"));
+        Self::write_png(&path, image)?;
+        Ok(path)
+    }
+
+    // Runs inside the frame loop, so write errors are logged and the frame dropped
+    pub fn submit_frame(&mut self, frame: TexReadback) {
+        let image = Self::to_rgba8(frame);
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            if let Err(err) = self.save_png(&format!("screenshot_{}", Self::timestamp()), &image) {
+                log::error!("Failed to save screenshot: {err}");
+            }
+        }
+
+        if let Some(recording) = self.recording.as_mut() {
+            if let Err(err) = Self::record_frame(recording, &image) {
+                log::error!("Failed to record frame, stopping the recording: {err}");
+                self.recording = None;
+            }
+        }
+    }
+
+    fn record_frame(recording: &mut Recording, image: &TextureData) -> io::Result<()> {
+        match recording.format {
+            RecordingFormat::Png => {
+                let path = recording
+                    .directory
+                    .join(format!("frame_{:06}.png", recording.frame));
+                Self::write_png(&path, image)?;
+            }
+            RecordingFormat::Y4m => {
+                if recording.y4m.is_none() {
+                    let file = File::create(recording.directory.join("recording.y4m"))?;
+                    let mut writer = BufWriter::new(file);
+                    writeln!(
+                        writer,
+                        "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                        image.width, image.height, recording.fps
+                    )?;
+                    recording.y4m = Some(writer);
+                }
+                Self::write_y4m_frame(recording.y4m.as_mut().unwrap(), image)?;
+            }
+        }
+
+        recording.frame += 1;
+        Ok(())
+    }
+
+    pub fn to_rgba8(frame: TexReadback) -> TextureData {
+        match frame {
+            TexReadback::RGBA8(image) => image,
+            TexReadback::Float {
+                width,
+                height,
+                layers,
+                channels,
+                data,
+            } => {
+                let mut bytes = Vec::with_capacity((4 * width * height * layers) as usize);
+                for texel in data.chunks_exact(channels as usize) {
+                    let value = |c: usize| (texel[c].clamp(0.0, 1.0) * 255.0).round() as u8;
+
+                    if channels == 1 {
+                        bytes.extend_from_slice(&[value(0), value(0), value(0), 255]);
+                    } else {
+                        bytes.extend_from_slice(&[value(0), value(1), value(2), value(3)]);
+                    }
+                }
+
+                TextureData {
+                    width,
+                    height: height * layers,
+                    data: bytes,
+                }
+            }
+        }
+    }
+
+    pub fn write_png(path: &Path, image: &TextureData) -> io::Result<()> {
+        image::save_buffer(
+            path,
+            image.data.as_slice(),
+            image.width,
+            image.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|err| match err {
+            image::ImageError::IoError(err) => err,
+            err => io::Error::other(err),
+        })
+    }
+
+    // Full range BT.601 conversion, one plane per channel
+    fn write_y4m_frame(writer: &mut BufWriter<File>, image: &TextureData) -> io::Result<()> {
+        let pixel_count = (image.width * image.height) as usize;
+        let mut planes = vec![0u8; 3 * pixel_count];
+
+        for (i, pixel) in image.data.chunks_exact(4).enumerate() {
+            let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+
+            planes[i] = (0.299 * r + 0.587 * g + 0.114 * b).round() as u8;
+            planes[pixel_count + i] = (128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+            planes[2 * pixel_count + i] = (128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+
+        writer.write_all(b"FRAME\n")?;
+        writer.write_all(planes.as_slice())
+    }
+
+    pub fn timestamp() -> u128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> TextureData {
+        TextureData {
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        }
+    }
+
+    // A file where the capture directory should be, so every write under it fails
+    fn blocked_dir(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("capture_{test}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::write(&path, b"").unwrap();
+        path
+    }
+
+    #[test]
+    fn save_png_reports_io_errors() {
+        let capture = FrameCapture::new(blocked_dir("save").to_str().unwrap());
+        assert!(capture.save_png("shot", &image()).is_err());
+    }
+
+    #[test]
+    fn failed_recording_writes_stop_the_recording() {
+        let dir = std::env::temp_dir().join(format!("capture_record_{}", std::process::id()));
+        let mut capture = FrameCapture::new(dir.to_str().unwrap());
+        capture.start_recording(RecordingFormat::Png, 30).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::write(&dir, b"").unwrap();
+        capture.submit_frame(TexReadback::RGBA8(image()));
+        assert!(!capture.is_recording());
+
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    capture::FrameCapture,
+    renderer::RendererState,
+    rendergraph::RenderGraph,
+    resource::{loadable::Loadable, texture::TextureData, ResourceManager},
+    world::{World, WorldBehavior},
+    EngineResources,
+};
+
+// Set to write the captured image as the new reference instead of comparing against it
+pub const BLESS_VAR: &str = "GOLDEN_BLESS";
+
+#[derive(Debug)]
+pub enum GoldenResult {
+    Passed,
+    ReferenceCreated(PathBuf),
+    MissingReference {
+        reference: PathBuf,
+        actual: PathBuf,
+    },
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+        image: PathBuf,
+    },
+    Failed {
+        mismatched_pixels: usize,
+        diff: PathBuf,
+    },
+}
+
+pub struct GoldenTest {
+    pub behaviors: Vec<Box<dyn WorldBehavior>>,
+    pub resource_root: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub dt: f32,
+    pub target_tag: &'static str,
+    pub target_index: usize,
+    pub reference: PathBuf,
+    pub tolerance: u8,
+    pub bless: bool,
+}
+
+impl GoldenResult {
+    pub fn passed(&self) -> bool {
+        matches!(
+            self,
+            GoldenResult::Passed | GoldenResult::ReferenceCreated(_)
+        )
+    }
+}
+
+impl GoldenTest {
+    pub fn new(
+        behaviors: Vec<Box<dyn WorldBehavior>>,
+        target_tag: &'static str,
+        reference: &str,
+    ) -> GoldenTest {
+        Self {
+            behaviors,
+            resource_root: "./res",
+            width: 256,
+            height: 256,
+            frames: 1,
+            dt: 1.0 / 60.0,
+            target_tag,
+            target_index: 0,
+            reference: PathBuf::from(reference),
+            tolerance: 2,
+            bless: std::env::var_os(BLESS_VAR).is_some(),
+        }
+    }
+
+    pub async fn run(self) -> GoldenResult {
+        let renderer = RendererState::new_headless(self.width, self.height).await;
+        let resource_manager = ResourceManager::new(self.resource_root, &renderer);
+
+        let mut resources = EngineResources {
+            renderer,
+            resource_manager,
+            render_graph: RenderGraph::new(),
+        };
+
+        let mut world = World::new(self.behaviors).unwrap();
+        world.init(&mut resources);
+
+        for _ in 0..self.frames {
+            resources.begin_frame();
+            world.run(self.dt, &mut resources);
+            resources.flush();
+            resources
+                .render_graph
+                .execute(&resources.renderer, &resources.resource_manager, None);
+            resources.end_frame();
+        }
+
+        let target_tag = self.target_tag;
+        let target_index = self.target_index;
+        let readback = world.with_renderer(&mut resources, |world, renderer| {
+            let entity = *world
+                .ids_by_tag(target_tag)
+                .first()
+                .unwrap_or_else(|| panic!("No entity tagged '{target_tag}' to capture"));
+            let render = world.get_render(entity).unwrap();
+
+            renderer.read_texture(render, target_index).unwrap()
+        });
+
+        let actual = FrameCapture::to_rgba8(readback.wait(&resources.renderer.device));
+
+        Self::compare(&self.reference, &actual, self.tolerance, self.bless)
+    }
+
+    pub fn compare(
+        reference: &Path,
+        actual: &TextureData,
+        tolerance: u8,
+        bless: bool,
+    ) -> GoldenResult {
+        if bless {
+            FrameCapture::write_png(reference, actual).unwrap();
+            return GoldenResult::ReferenceCreated(reference.to_path_buf());
+        }
+
+        let expected = match TextureData::load(reference.to_str().unwrap()) {
+            Ok(expected) => expected,
+            Err(_) => {
+                let actual_path = reference.with_extension("actual.png");
+                FrameCapture::write_png(&actual_path, actual).unwrap();
+                return GoldenResult::MissingReference {
+                    reference: reference.to_path_buf(),
+                    actual: actual_path,
+                };
+            }
+        };
+
+        if expected.width != actual.width || expected.height != actual.height {
+            let image = reference.with_extension("actual.png");
+            FrameCapture::write_png(&image, actual).unwrap();
+
+            return GoldenResult::SizeMismatch {
+                expected: (expected.width, expected.height),
+                actual: (actual.width, actual.height),
+                image,
+            };
+        }
+
+        let mut mismatched_pixels = 0;
+        let mut diff_data = Vec::with_capacity(actual.data.len());
+
+        for (expected, actual) in expected
+            .data
+            .chunks_exact(4)
+            .zip(actual.data.chunks_exact(4))
+        {
+            let delta = expected
+                .iter()
+                .zip(actual.iter())
+                .map(|(e, a)| e.abs_diff(*a))
+                .max()
+                .unwrap();
+
+            if delta > tolerance {
+                mismatched_pixels += 1;
+                diff_data.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                let luma = (actual[0] as u32 + actual[1] as u32 + actual[2] as u32) / 12;
+                diff_data.extend_from_slice(&[luma as u8, luma as u8, luma as u8, 255]);
+            }
+        }
+
+        if mismatched_pixels == 0 {
+            return GoldenResult::Passed;
+        }
+
+        let diff = reference.with_extension("diff.png");
+        FrameCapture::write_png(
+            &diff,
+            &TextureData {
+                width: actual.width,
+                height: actual.height,
+                data: diff_data,
+            },
+        )
+        .unwrap();
+        FrameCapture::write_png(&reference.with_extension("actual.png"), actual).unwrap();
+
+        GoldenResult::Failed {
+            mismatched_pixels,
+            diff,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec3;
+    use wgpu::Color;
+
+    use super::*;
+    use crate::engine::{
+        resource::model::ModelVertex,
+        world::{
+            camera::Camera,
+            entity_renderer::{
+                EntityModel, EntityRenderer, EntityTexture, RenderTargetType, SetVerticesData,
+            },
+            id::EntityId,
+        },
+    };
+
+    fn image(pixel: [u8; 4]) -> TextureData {
+        TextureData {
+            width: 2,
+            height: 2,
+            data: pixel.repeat(4),
+        }
+    }
+
+    // Each test gets its own directory so they can run in parallel
+    fn reference(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("golden_{test}_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reference.png");
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn identical_images_pass() {
+        let path = reference("identical");
+        FrameCapture::write_png(&path, &image([10, 20, 30, 255])).unwrap();
+
+        let result = GoldenTest::compare(&path, &image([10, 20, 30, 255]), 0, false);
+        assert!(matches!(result, GoldenResult::Passed));
+    }
+
+    #[test]
+    fn differences_within_tolerance_pass() {
+        let path = reference("within");
+        FrameCapture::write_png(&path, &image([10, 20, 30, 255])).unwrap();
+
+        let result = GoldenTest::compare(&path, &image([12, 18, 30, 255]), 2, false);
+        assert!(matches!(result, GoldenResult::Passed));
+    }
+
+    #[test]
+    fn differences_over_tolerance_fail_with_a_diff() {
+        let path = reference("over");
+        FrameCapture::write_png(&path, &image([10, 20, 30, 255])).unwrap();
+
+        match GoldenTest::compare(&path, &image([13, 20, 30, 255]), 2, false) {
+            GoldenResult::Failed {
+                mismatched_pixels,
+                diff,
+            } => {
+                assert_eq!(mismatched_pixels, 4);
+                assert!(diff.exists());
+            }
+            result => panic!("Expected a failure, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn size_mismatch_fails() {
+        let path = reference("size");
+        FrameCapture::write_png(&path, &image([10, 20, 30, 255])).unwrap();
+
+        let actual = TextureData {
+            width: 1,
+            height: 1,
+            data: vec![10, 20, 30, 255],
+        };
+        match GoldenTest::compare(&path, &actual, 255, false) {
+            GoldenResult::SizeMismatch {
+                expected,
+                actual,
+                image,
+            } => {
+                assert_eq!((expected, actual), ((2, 2), (1, 1)));
+                assert!(image.exists());
+            }
+            result => panic!("Expected a size mismatch, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_reference_fails_unless_blessed() {
+        let path = reference("missing");
+
+        let result = GoldenTest::compare(&path, &image([1, 2, 3, 255]), 0, false);
+        assert!(matches!(result, GoldenResult::MissingReference { .. }));
+        assert!(!result.passed());
+        assert!(!path.exists());
+
+        let result = GoldenTest::compare(&path, &image([1, 2, 3, 255]), 0, true);
+        assert!(matches!(result, GoldenResult::ReferenceCreated(_)));
+        assert!(path.exists());
+    }
+
+    // A 16x16 target tagged "target", watched by a red clearing camera 5 units down +z
+    fn create_target(world: &mut World, renderer: &mut EntityRenderer) {
+        let target = renderer
+            .create_render(
+                Some(EntityTexture::RenderTarget {
+                    width: 16,
+                    height: 16,
+                    ty: RenderTargetType::RGBA32Depth,
+                    post_enabled: false,
+                }),
+                None,
+            )
+            .unwrap();
+        world
+            .create_entity(&["target"], Some(target), renderer)
+            .unwrap();
+
+        let camera = world.create_entity(&[], None, renderer).unwrap();
+        world.transform_by_id(camera).unwrap().pos = vec3(0.0, 0.0, 5.0);
+
+        let mut settings = Camera::perspective(60.0, 0.1, 100.0);
+        settings.target = Some(target);
+        settings.clear_color = Color::RED;
+        world.set_camera(camera, settings).unwrap();
+    }
+
+    struct ClearTarget;
+
+    impl WorldBehavior for ClearTarget {
+        fn tags(&mut self) -> &[&'static str] {
+            &[]
+        }
+
+        fn init(&mut self, world: &mut World, renderer: &mut EntityRenderer) {
+            create_target(world, renderer);
+        }
+
+        fn on_created(&mut self, _: &mut World, _: &mut EntityRenderer, _: &[EntityId]) {}
+        fn on_deleted(&mut self, _: &mut World, _: &mut EntityRenderer, _: &[EntityId]) {}
+        fn run(&mut self, _: &mut World, _: &mut EntityRenderer, _: f32) {}
+    }
+
+    // An untextured quad facing the camera and covering its whole view
+    struct DrawQuad;
+
+    impl WorldBehavior for DrawQuad {
+        fn tags(&mut self) -> &[&'static str] {
+            &[]
+        }
+
+        fn init(&mut self, world: &mut World, renderer: &mut EntityRenderer) {
+            create_target(world, renderer);
+
+            let quad = renderer
+                .create_render(None, Some(EntityModel::InitialSize(6)))
+                .unwrap();
+            let vertex = |x: f32, y: f32| ModelVertex {
+                pos: [x * 100.0, y * 100.0, 0.0],
+                normal: [0.0, 0.0, 1.0],
+                uv: [0.0, 0.0],
+            };
+            let vertices = [
+                vertex(-1.0, -1.0),
+                vertex(1.0, -1.0),
+                vertex(1.0, 1.0),
+                vertex(-1.0, -1.0),
+                vertex(1.0, 1.0),
+                vertex(-1.0, 1.0),
+            ];
+            renderer
+                .set_vertices(quad, SetVerticesData::Replace(&vertices))
+                .unwrap();
+            world.create_entity(&[], Some(quad), renderer).unwrap();
+        }
+
+        fn on_created(&mut self, _: &mut World, _: &mut EntityRenderer, _: &[EntityId]) {}
+        fn on_deleted(&mut self, _: &mut World, _: &mut EntityRenderer, _: &[EntityId]) {}
+        fn run(&mut self, _: &mut World, _: &mut EntityRenderer, _: f32) {}
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn camera_clears_its_target() {
+        let mut test = GoldenTest::new(
+            vec![Box::new(ClearTarget)],
+            "target",
+            "res/golden/clear_target.png",
+        );
+        test.width = 16;
+        test.height = 16;
+
+        let result = pollster::block_on(test.run());
+        assert!(result.passed(), "{result:?}");
+    }
+
+    // The quad is white lit by the shader's key light, max(dot(z, light), 0) * 0.7 + 0.3 = 0.64
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn camera_draws_meshes_into_a_float_target() {
+        let mut test = GoldenTest::new(
+            vec![Box::new(DrawQuad)],
+            "target",
+            "res/golden/draw_quad.png",
+        );
+        test.width = 16;
+        test.height = 16;
+
+        let result = pollster::block_on(test.run());
+        assert!(result.passed(), "{result:?}");
+    }
+}
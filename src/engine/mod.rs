@@ -4,15 +4,52 @@ use winit::{
     window::WindowBuilder,
 };
 
-use self::{renderer::RendererState, resource::ResourceManager, world::World};
+use self::{
+    capture::RecordingFormat,
+    renderer::RendererState,
+    rendergraph::RenderGraph,
+    resource::ResourceManager,
+    world::{
+        events::{FileDropped, WindowFocused, WindowResized},
+        World,
+    },
+};
 
+pub mod capture;
+#[cfg(test)]
+pub mod golden;
 pub mod renderer;
+pub mod rendergraph;
 pub mod resource;
 pub mod world;
 
+const MAX_FRAME_DT: f32 = 0.25;
+
 pub struct EngineResources {
     renderer: RendererState,
     resource_manager: ResourceManager,
+    render_graph: RenderGraph,
+}
+
+impl EngineResources {
+    fn begin_frame(&mut self) {
+        self.resource_manager.poll_shaders();
+    }
+
+    // Runs after the world so textures allocated this frame have bind group pages before drawing
+    fn flush(&mut self) {
+        self.resource_manager
+            .atlas
+            .flush(&self.renderer.queue, &mut self.resource_manager.tex_manager);
+        self.resource_manager
+            .tex_manager
+            .flush(&self.renderer.device);
+    }
+
+    fn end_frame(&mut self) {
+        self.renderer.device.poll(wgpu::Maintain::Poll);
+        self.resource_manager.frame_alloc.reset();
+    }
 }
 
 pub async fn start(mut world: World) {
@@ -28,11 +65,12 @@ pub async fn start(mut world: World) {
     let mut resources = EngineResources {
         renderer,
         resource_manager,
+        render_graph: RenderGraph::new(),
     };
 
     world.init(&mut resources);
 
-    let mut last_frame = std::time::Instant::now() - std::time::Duration::from_millis(10);
+    let mut last_frame = std::time::Instant::now();
     event_loop.run(move |event, _, flow| {
         match event {
             Event::WindowEvent { event, .. } => match event {
@@ -42,16 +80,75 @@ pub async fn start(mut world: World) {
                 }
                 WindowEvent::Resized(size) => {
                     resources.renderer.resize(size);
+                    world.send(WindowResized {
+                        width: size.width,
+                        height: size.height,
+                    });
+                }
+                WindowEvent::Focused(focused) => world.send(WindowFocused(focused)),
+                WindowEvent::DroppedFile(path) => world.send(FileDropped(path)),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } => {
+                    let capture = &mut resources.renderer.capture;
+                    if capture.screenshot_key == Some(key) {
+                        capture.request_screenshot();
+                    } else if capture.record_key == Some(key) {
+                        if let Err(err) = capture.toggle_recording(RecordingFormat::Png, 60) {
+                            log::error!("Failed to toggle recording: {err}");
+                        }
+                    }
                 }
                 _ => {}
             },
             Event::MainEventsCleared => {
                 let this_frame = std::time::Instant::now();
-                let dt = (this_frame - last_frame).as_secs_f32();
+                let dt = resources
+                    .renderer
+                    .capture
+                    .fixed_dt()
+                    .unwrap_or((this_frame - last_frame).as_secs_f32().min(MAX_FRAME_DT));
+
+                resources.begin_frame();
+                resources.renderer.sync_capture_usage();
 
-                let surface = resources.renderer.surface.get_current_texture().unwrap();
+                let surface = resources
+                    .renderer
+                    .surface
+                    .as_ref()
+                    .unwrap()
+                    .get_current_texture()
+                    .unwrap();
                 world.run(dt, &mut resources);
+                resources.flush();
+
+                let view = surface
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                resources.render_graph.execute(
+                    &resources.renderer,
+                    &resources.resource_manager,
+                    Some(&view),
+                );
+
+                // Requests made during this frame wait for the next one, this surface isn't copyable yet
+                let copyable = resources
+                    .renderer
+                    .surface_config
+                    .usage
+                    .contains(wgpu::TextureUsages::COPY_SRC);
+                if copyable && resources.renderer.capture.wants_frame() {
+                    resources.renderer.capture_surface(&surface.texture);
+                }
+
                 surface.present();
+                resources.end_frame();
 
                 last_frame = this_frame;
             }
@@ -1,12 +1,23 @@
+use std::path::PathBuf;
+
 use wgpu::*;
 use winit::{dpi::PhysicalSize, window::Window};
 
+use super::{
+    capture::FrameCapture,
+    resource::{
+        readback::{read_texture, ReadbackError},
+        texmanager::{TexHandle, TexManager},
+    },
+};
+
 pub struct RendererState {
-    pub surface: Surface,
+    pub surface: Option<Surface>,
     pub device: Device,
     pub queue: Queue,
     pub surface_config: SurfaceConfiguration,
     pub size: PhysicalSize<u32>,
+    pub capture: FrameCapture,
 }
 
 impl RendererState {
@@ -14,11 +25,61 @@ impl RendererState {
         let size = window.inner_size();
         let instance = Instance::new(wgpu::Backends::all());
         let surface = unsafe { instance.create_surface(window) };
+        let (adapter, device, queue) = Self::request_device(&instance, Some(&surface)).await;
+
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: surface.get_supported_formats(&adapter)[0],
+            width: size.width,
+            height: size.height,
+            present_mode: PresentMode::AutoVsync,
+            alpha_mode: CompositeAlphaMode::Auto,
+        };
+
+        surface.configure(&device, &config);
+
+        Self {
+            surface: Some(surface),
+            device,
+            queue,
+            surface_config: config,
+            size,
+            capture: FrameCapture::new("./captures"),
+        }
+    }
+
+    pub async fn new_headless(width: u32, height: u32) -> RendererState {
+        let instance = Instance::new(wgpu::Backends::all());
+        let (_, device, queue) = Self::request_device(&instance, None).await;
+
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: PresentMode::AutoVsync,
+            alpha_mode: CompositeAlphaMode::Auto,
+        };
+
+        Self {
+            surface: None,
+            device,
+            queue,
+            surface_config: config,
+            size: PhysicalSize::new(width, height),
+            capture: FrameCapture::new("./captures"),
+        }
+    }
+
+    async fn request_device(
+        instance: &Instance,
+        surface: Option<&Surface>,
+    ) -> (Adapter, Device, Queue) {
         let adapter = instance
             .request_adapter(&RequestAdapterOptions {
                 power_preference: PowerPreference::HighPerformance,
                 force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
+                compatible_surface: surface,
             })
             .await
             .unwrap();
@@ -39,24 +100,7 @@ impl RendererState {
             .await
             .unwrap();
 
-        let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
-            width: size.width,
-            height: size.height,
-            present_mode: PresentMode::AutoVsync,
-            alpha_mode: CompositeAlphaMode::Auto,
-        };
-
-        surface.configure(&device, &config);
-
-        Self {
-            surface,
-            device,
-            queue,
-            surface_config: config,
-            size,
-        }
+        (adapter, device, queue)
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
@@ -64,6 +108,58 @@ impl RendererState {
         self.surface_config.width = size.width;
         self.surface_config.height = size.height;
 
-        self.surface.configure(&self.device, &self.surface_config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    // The swapchain is only made copyable while a screenshot or recording needs its frames
+    pub fn sync_capture_usage(&mut self) {
+        let usage = if self.capture.wants_frame() {
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC
+        } else {
+            TextureUsages::RENDER_ATTACHMENT
+        };
+
+        if usage != self.surface_config.usage {
+            self.surface_config.usage = usage;
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.surface_config);
+            }
+        }
+    }
+
+    pub fn capture_surface(&mut self, texture: &Texture) {
+        let readback = read_texture(
+            &self.device,
+            &self.queue,
+            texture,
+            self.surface_config.format,
+            Extent3d {
+                width: self.surface_config.width,
+                height: self.surface_config.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        match readback {
+            Ok(readback) => self.capture.submit_frame(readback.wait(&self.device)),
+            Err(err) => log::error!("Failed to capture frame: {err}"),
+        }
+    }
+
+    pub fn capture_target(
+        &self,
+        tex_manager: &TexManager,
+        target: &TexHandle,
+    ) -> Result<PathBuf, ReadbackError> {
+        let frame = tex_manager
+            .read_tex(&self.device, &self.queue, target)?
+            .wait(&self.device);
+
+        Ok(self.capture.save_png(
+            &format!("target_{}", FrameCapture::timestamp()),
+            &FrameCapture::to_rgba8(frame),
+        )?)
     }
 }
@@ -0,0 +1,392 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::Write,
+};
+
+use wgpu::*;
+
+use super::{
+    renderer::RendererState,
+    resource::{
+        texmanager::{TexHandle, TexId},
+        ResourceManager,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GraphResource {
+    Texture(TexId),
+    Transient(usize),
+    Surface,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TransientDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+}
+
+pub struct PassContext<'a> {
+    pub encoder: &'a mut CommandEncoder,
+    pub reads: Vec<&'a TextureView>,
+    pub colors: Vec<&'a TextureView>,
+    pub depth: Option<&'a TextureView>,
+    pub renderer: &'a RendererState,
+    pub resources: &'a ResourceManager,
+}
+
+struct PassNode {
+    name: String,
+    reads: Vec<GraphResource>,
+    colors: Vec<GraphResource>,
+    depth: Option<GraphResource>,
+    run: Box<dyn FnOnce(&mut PassContext)>,
+}
+
+impl PassNode {
+    fn writes(&self) -> impl Iterator<Item = &GraphResource> {
+        self.colors.iter().chain(self.depth.iter())
+    }
+}
+
+struct PooledTarget {
+    desc: TransientDesc,
+    view: Option<TextureView>,
+    busy_until: Option<usize>,
+}
+
+pub struct CompiledGraph {
+    pub order: Vec<usize>,
+    aliases: Vec<usize>,
+}
+
+pub struct RenderGraph {
+    passes: Vec<PassNode>,
+    transients: Vec<TransientDesc>,
+    pool: Vec<PooledTarget>,
+}
+
+impl RenderGraph {
+    pub fn new() -> RenderGraph {
+        Self {
+            passes: Vec::new(),
+            transients: Vec::new(),
+            pool: Vec::new(),
+        }
+    }
+
+    pub fn import(&self, handle: &TexHandle) -> GraphResource {
+        GraphResource::Texture(handle.id())
+    }
+
+    pub fn create_transient(&mut self, desc: TransientDesc) -> GraphResource {
+        self.transients.push(desc);
+        GraphResource::Transient(self.transients.len() - 1)
+    }
+
+    pub fn add_pass(
+        &mut self,
+        name: &str,
+        reads: &[GraphResource],
+        colors: &[GraphResource],
+        depth: Option<GraphResource>,
+        run: impl FnOnce(&mut PassContext) + 'static,
+    ) {
+        self.passes.push(PassNode {
+            name: String::from(name),
+            reads: reads.to_vec(),
+            colors: colors.to_vec(),
+            depth,
+            run: Box::new(run),
+        });
+    }
+
+    // Readers follow the last writer declared before them, and a writer waits for the previous
+    // writer and every reader since it, so each pass sees the same data as in declaration order
+    pub fn compile(&mut self) -> CompiledGraph {
+        let mut edges = vec![Vec::new(); self.passes.len()];
+        let mut last_writer: HashMap<GraphResource, usize> = HashMap::new();
+        let mut readers: HashMap<GraphResource, Vec<usize>> = HashMap::new();
+
+        for (idx, pass) in self.passes.iter().enumerate() {
+            for res in pass.reads.iter() {
+                if let Some(&writer) = last_writer.get(res) {
+                    edges[writer].push(idx);
+                }
+                readers.entry(*res).or_default().push(idx);
+            }
+
+            for res in pass.writes() {
+                for reader in readers.remove(res).into_iter().flatten() {
+                    if reader != idx {
+                        edges[reader].push(idx);
+                    }
+                }
+                if let Some(writer) = last_writer.insert(*res, idx) {
+                    if writer != idx {
+                        edges[writer].push(idx);
+                    }
+                }
+            }
+        }
+
+        let mut in_degree = vec![0; self.passes.len()];
+        for &to in edges.iter().flatten() {
+            in_degree[to] += 1;
+        }
+
+        let mut ready = BinaryHeap::from_iter(
+            (0..self.passes.len())
+                .filter(|&idx| in_degree[idx] == 0)
+                .map(Reverse),
+        );
+        let mut order = Vec::with_capacity(self.passes.len());
+
+        while let Some(Reverse(idx)) = ready.pop() {
+            order.push(idx);
+            for &to in edges[idx].iter() {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.push(Reverse(to));
+                }
+            }
+        }
+
+        if order.len() != self.passes.len() {
+            panic!("Render graph has a cycle!");
+        }
+
+        let aliases = self.alias_transients(&order);
+
+        CompiledGraph { order, aliases }
+    }
+
+    pub fn execute(
+        &mut self,
+        renderer: &RendererState,
+        resources: &ResourceManager,
+        surface: Option<&TextureView>,
+    ) {
+        if self.passes.is_empty() {
+            return;
+        }
+
+        let compiled = self.compile();
+        self.realize_pool(&renderer.device);
+
+        let mut passes = Vec::from_iter(self.passes.drain(..).map(Some));
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("RenderGraph"),
+            });
+
+        for idx in compiled.order {
+            let pass = passes[idx].take().unwrap();
+            let view = |res: &GraphResource, attachment: bool| match res {
+                GraphResource::Texture(id) if attachment => {
+                    resources.tex_manager.get_attachment_view(*id)
+                }
+                GraphResource::Texture(id) => resources.tex_manager.get_view(*id),
+                GraphResource::Transient(t) => {
+                    self.pool[compiled.aliases[*t]].view.as_ref().unwrap()
+                }
+                GraphResource::Surface => surface.expect("Render graph has no surface to draw to"),
+            };
+
+            let mut ctx = PassContext {
+                encoder: &mut encoder,
+                reads: Vec::from_iter(pass.reads.iter().map(|res| view(res, false))),
+                colors: Vec::from_iter(pass.colors.iter().map(|res| view(res, true))),
+                depth: pass.depth.as_ref().map(|res| view(res, true)),
+                renderer,
+                resources,
+            };
+
+            (pass.run)(&mut ctx);
+        }
+
+        renderer.queue.submit(Some(encoder.finish()));
+        self.transients.clear();
+    }
+
+    pub fn dump_dot(&mut self) -> String {
+        let compiled = self.compile();
+        let mut dot = String::from("digraph RenderGraph {\n    rankdir=LR;\n");
+
+        for (position, &idx) in compiled.order.iter().enumerate() {
+            let pass = &self.passes[idx];
+            writeln!(
+                dot,
+                "    pass{idx} [shape=box, label=\"{position}: {}\"];",
+                pass.name
+            )
+            .unwrap();
+
+            for res in pass.reads.iter() {
+                writeln!(dot, "    \"{res:?}\" -> pass{idx};").unwrap();
+            }
+            for res in pass.writes() {
+                writeln!(dot, "    pass{idx} -> \"{res:?}\";").unwrap();
+            }
+        }
+
+        for (t, &slot) in compiled.aliases.iter().enumerate() {
+            writeln!(
+                dot,
+                "    \"{:?}\" [shape=ellipse, label=\"Transient({t}) -> pool {slot}\"];",
+                GraphResource::Transient(t)
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Transients whose lifetimes don't overlap share a pooled texture with the same description
+    fn alias_transients(&mut self, order: &[usize]) -> Vec<usize> {
+        let mut lifetimes = vec![None; self.transients.len()];
+
+        for (position, &idx) in order.iter().enumerate() {
+            let pass = &self.passes[idx];
+            for res in pass.reads.iter().chain(pass.writes()) {
+                if let GraphResource::Transient(t) = res {
+                    let (first, _) = lifetimes[*t].unwrap_or((position, position));
+                    lifetimes[*t] = Some((first, position));
+                }
+            }
+        }
+
+        for target in self.pool.iter_mut() {
+            target.busy_until = None;
+        }
+
+        let mut by_first_use = Vec::from_iter(0..self.transients.len());
+        by_first_use.sort_by_key(|&t| lifetimes[t].map(|(first, _)| first));
+
+        let mut aliases = vec![0; self.transients.len()];
+        for t in by_first_use {
+            let desc = self.transients[t];
+            let (first, last) = lifetimes[t].unwrap_or((0, 0));
+
+            let free = self.pool.iter().position(|target| {
+                target.desc == desc && target.busy_until.is_none_or(|busy| busy < first)
+            });
+
+            let slot = match free {
+                Some(slot) => slot,
+                None => {
+                    self.pool.push(PooledTarget {
+                        desc,
+                        view: None,
+                        busy_until: None,
+                    });
+                    self.pool.len() - 1
+                }
+            };
+
+            self.pool[slot].busy_until = Some(last);
+            aliases[t] = slot;
+        }
+
+        aliases
+    }
+
+    fn realize_pool(&mut self, device: &Device) {
+        for target in self.pool.iter_mut().filter(|target| target.view.is_none()) {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("RenderGraph Transient"),
+                size: Extent3d {
+                    width: target.desc.width,
+                    height: target.desc.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: target.desc.format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            });
+
+            target.view = Some(texture.create_view(&TextureViewDescriptor::default()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(passes: &[(&[GraphResource], &[GraphResource])]) -> Vec<usize> {
+        let mut graph = RenderGraph::new();
+        transient(&mut graph);
+        transient(&mut graph);
+        for (idx, (reads, colors)) in passes.iter().enumerate() {
+            graph.add_pass(&format!("{idx}"), reads, colors, None, |_| {});
+        }
+        graph.compile().order
+    }
+
+    fn transient(graph: &mut RenderGraph) -> GraphResource {
+        graph.create_transient(TransientDesc {
+            width: 4,
+            height: 4,
+            format: TextureFormat::Rgba8Unorm,
+        })
+    }
+
+    fn position(order: &[usize], pass: usize) -> usize {
+        order.iter().position(|&idx| idx == pass).unwrap()
+    }
+
+    #[test]
+    fn reader_sees_last_earlier_writer() {
+        let x = GraphResource::Transient(0);
+        // A writes X, B reads X, C writes X
+        let order = graph(&[(&[], &[x]), (&[x], &[]), (&[], &[x])]);
+        assert!(position(&order, 0) < position(&order, 1));
+        assert!(position(&order, 1) < position(&order, 2));
+    }
+
+    #[test]
+    fn write_after_read_waits_for_reader() {
+        let x = GraphResource::Transient(0);
+        let y = GraphResource::Transient(1);
+        // Pass 2 only writes X, but must not run before pass 1 has read the old contents
+        let order = graph(&[(&[], &[y]), (&[x, y], &[]), (&[], &[x])]);
+        assert!(position(&order, 1) < position(&order, 2));
+    }
+
+    #[test]
+    fn writes_keep_declaration_order() {
+        let x = GraphResource::Transient(0);
+        let order = graph(&[(&[], &[x]), (&[], &[x]), (&[], &[x])]);
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn independent_passes_keep_declaration_order() {
+        let x = GraphResource::Transient(0);
+        let y = GraphResource::Transient(1);
+        let order = graph(&[(&[x], &[y]), (&[], &[x])]);
+        assert_eq!(order, vec![0, 1]);
+    }
+
+    #[test]
+    fn transients_alias_when_lifetimes_dont_overlap() {
+        let mut graph = RenderGraph::new();
+        let a = transient(&mut graph);
+        let b = transient(&mut graph);
+        let out = GraphResource::Surface;
+        graph.add_pass("A", &[], &[a], None, |_| {});
+        graph.add_pass("UseA", &[a], &[out], None, |_| {});
+        graph.add_pass("B", &[], &[b], None, |_| {});
+        graph.add_pass("UseB", &[b], &[out], None, |_| {});
+
+        let compiled = graph.compile();
+        assert_eq!(compiled.aliases[0], compiled.aliases[1]);
+    }
+}
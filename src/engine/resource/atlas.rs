@@ -0,0 +1,208 @@
+use wgpu::*;
+
+use super::{
+    texmanager::{TexDataFormat, TexHandle, TexManager},
+    texture::TextureData,
+};
+
+pub struct AtlasHandle {
+    pub page: usize,
+    pub uv: [f32; 4],
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+struct AtlasPage {
+    tex: TexHandle,
+    data: TextureData,
+    shelves: Vec<Shelf>,
+    live: usize,
+    dirty: bool,
+}
+
+pub struct TexAtlas {
+    pages: Vec<AtlasPage>,
+    page_size: u32,
+    padding: u32,
+    max_entry_size: u32,
+}
+
+impl TexAtlas {
+    pub fn new(page_size: u32, padding: u32, max_entry_size: u32) -> TexAtlas {
+        Self {
+            pages: Vec::new(),
+            page_size,
+            padding,
+            max_entry_size,
+        }
+    }
+
+    // Empty images are rejected too, the edge extrusion in `blit` needs at least one pixel
+    pub fn fits(&self, data: &TextureData) -> bool {
+        data.width > 0
+            && data.height > 0
+            && data.width <= self.max_entry_size
+            && data.height <= self.max_entry_size
+    }
+
+    pub fn alloc(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        tex_manager: &mut TexManager,
+        data: &TextureData,
+    ) -> AtlasHandle {
+        if !self.fits(data) {
+            panic!("Texture is empty or too large for the atlas!");
+        }
+
+        let w = data.width + 2 * self.padding;
+        let h = data.height + 2 * self.padding;
+
+        let (page, x, y) = match self.find_space(w, h) {
+            Some(space) => space,
+            None => {
+                self.add_page(device, queue, tex_manager);
+                self.find_space(w, h).unwrap()
+            }
+        };
+
+        self.blit(page, x, y, data);
+
+        let atlas_page = &mut self.pages[page];
+        atlas_page.live += 1;
+        atlas_page.dirty = true;
+
+        let size = self.page_size as f32;
+        let (u0, v0) = (x + self.padding, y + self.padding);
+
+        AtlasHandle {
+            page,
+            uv: [
+                u0 as f32 / size,
+                v0 as f32 / size,
+                (u0 + data.width) as f32 / size,
+                (v0 + data.height) as f32 / size,
+            ],
+        }
+    }
+
+    pub fn free(&mut self, handle: AtlasHandle) {
+        let page = &mut self.pages[handle.page];
+        page.live -= 1;
+
+        if page.live == 0 {
+            page.shelves.clear();
+            page.data.data.fill(0);
+            page.dirty = true;
+        }
+    }
+
+    pub fn page_tex(&self, page: usize) -> &TexHandle {
+        &self.pages[page].tex
+    }
+
+    pub fn flush(&mut self, queue: &Queue, tex_manager: &mut TexManager) {
+        for page in self.pages.iter_mut().filter(|page| page.dirty) {
+            tex_manager.set_data(queue, &page.tex, &page.data).unwrap();
+            page.dirty = false;
+        }
+    }
+
+    fn add_page(&mut self, device: &Device, queue: &Queue, tex_manager: &mut TexManager) {
+        let data = TextureData {
+            width: self.page_size,
+            height: self.page_size,
+            data: vec![0; (4 * self.page_size * self.page_size) as usize],
+        };
+
+        self.pages.push(AtlasPage {
+            tex: tex_manager
+                .alloc_tex(device, queue, TexDataFormat::StaticRGBA8(&data))
+                .unwrap(),
+            data,
+            shelves: Vec::new(),
+            live: 0,
+            dirty: false,
+        });
+    }
+
+    // Best-fit shelf packing: reuse the shortest shelf that fits, otherwise open a new one
+    fn find_space(&mut self, w: u32, h: u32) -> Option<(usize, u32, u32)> {
+        for (page_idx, page) in self.pages.iter_mut().enumerate() {
+            let best = page
+                .shelves
+                .iter_mut()
+                .filter(|shelf| shelf.height >= h && shelf.x + w <= self.page_size)
+                .min_by_key(|shelf| shelf.height);
+
+            if let Some(shelf) = best {
+                let x = shelf.x;
+                shelf.x += w;
+                return Some((page_idx, x, shelf.y));
+            }
+
+            let top = page
+                .shelves
+                .last()
+                .map(|shelf| shelf.y + shelf.height)
+                .unwrap_or(0);
+
+            if top + h <= self.page_size && w <= self.page_size {
+                page.shelves.push(Shelf {
+                    y: top,
+                    height: h,
+                    x: w,
+                });
+                return Some((page_idx, 0, top));
+            }
+        }
+
+        None
+    }
+
+    // Copies the image with its edge pixels extruded into the padding so filtering and
+    // lower mips don't bleed neighbouring entries in
+    fn blit(&mut self, page: usize, x: u32, y: u32, data: &TextureData) {
+        let page_data = &mut self.pages[page].data;
+        let padded_w = data.width + 2 * self.padding;
+        let padded_h = data.height + 2 * self.padding;
+
+        for py in 0..padded_h {
+            let sy = py.saturating_sub(self.padding).min(data.height - 1);
+            for px in 0..padded_w {
+                let sx = px.saturating_sub(self.padding).min(data.width - 1);
+
+                let src = (4 * (sy * data.width + sx)) as usize;
+                let dst = (4 * ((y + py) * self.page_size + x + px)) as usize;
+                page_data.data[dst..dst + 4].copy_from_slice(&data.data[src..src + 4]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32) -> TextureData {
+        TextureData {
+            width,
+            height,
+            data: vec![0; (4 * width * height) as usize],
+        }
+    }
+
+    #[test]
+    fn fits_rejects_empty_and_oversized_images() {
+        let atlas = TexAtlas::new(256, 2, 64);
+        assert!(atlas.fits(&image(64, 1)));
+        assert!(!atlas.fits(&image(0, 16)));
+        assert!(!atlas.fits(&image(16, 0)));
+        assert!(!atlas.fits(&image(65, 16)));
+    }
+}
@@ -0,0 +1,157 @@
+use std::num::NonZeroU64;
+
+use wgpu::*;
+
+const FRAMES_IN_FLIGHT: u64 = 3;
+
+pub struct FrameAllocator {
+    pub uniform_buffer: Buffer,
+    pub storage_buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+    segment_size: u64,
+    uniform_window: u64,
+    uniform_alignment: u64,
+    storage_alignment: u64,
+    frame: u64,
+    uniform_offset: u64,
+    storage_offset: u64,
+}
+
+impl FrameAllocator {
+    pub fn new(device: &Device, segment_size: u64) -> FrameAllocator {
+        let limits = device.limits();
+        let uniform_window = segment_size.min(limits.max_uniform_buffer_binding_size as u64);
+
+        // Each frame gets its own segment; the trailing window keeps the last binding in bounds
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("FrameAllocator Uniforms"),
+            size: segment_size * FRAMES_IN_FLIGHT + uniform_window,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("FrameAllocator Storage"),
+            size: segment_size * (FRAMES_IN_FLIGHT + 1),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("FrameAllocator Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("FrameAllocator Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &uniform_buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(uniform_window),
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &storage_buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(segment_size),
+                    }),
+                },
+            ],
+        });
+
+        Self {
+            uniform_buffer,
+            storage_buffer,
+            bind_group_layout,
+            bind_group,
+            segment_size,
+            uniform_window,
+            uniform_alignment: limits.min_uniform_buffer_offset_alignment as u64,
+            storage_alignment: limits.min_storage_buffer_offset_alignment as u64,
+            frame: 0,
+            uniform_offset: 0,
+            storage_offset: 0,
+        }
+    }
+
+    pub fn alloc_uniform<T: bytemuck::Pod>(&mut self, queue: &Queue, data: &[T]) -> u32 {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        if bytes.len() as u64 > self.uniform_window {
+            panic!("Uniform data is larger than the FrameAllocator binding!");
+        }
+
+        let offset = Self::bump(
+            &mut self.uniform_offset,
+            bytes.len() as u64,
+            self.uniform_alignment,
+            self.segment_size,
+        );
+        let offset = self.segment_base() + offset;
+
+        queue.write_buffer(&self.uniform_buffer, offset, bytes);
+        offset as u32
+    }
+
+    pub fn alloc_storage<T: bytemuck::Pod>(&mut self, queue: &Queue, data: &[T]) -> u32 {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+
+        let offset = Self::bump(
+            &mut self.storage_offset,
+            bytes.len() as u64,
+            self.storage_alignment,
+            self.segment_size,
+        );
+        let offset = self.segment_base() + offset;
+
+        queue.write_buffer(&self.storage_buffer, offset, bytes);
+        offset as u32
+    }
+
+    pub fn reset(&mut self) {
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+        self.uniform_offset = 0;
+        self.storage_offset = 0;
+    }
+
+    fn segment_base(&self) -> u64 {
+        self.frame * self.segment_size
+    }
+
+    fn bump(cursor: &mut u64, size: u64, alignment: u64, capacity: u64) -> u64 {
+        let offset = cursor.next_multiple_of(alignment);
+        if offset + size > capacity {
+            panic!("FrameAllocator is out of space!");
+        }
+
+        *cursor = offset + size;
+        offset
+    }
+}
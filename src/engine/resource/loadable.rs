@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{rc::Rc, time::SystemTime};
 
 pub trait Loadable<T> {
     fn load(path: &str) -> Result<T, std::io::Error>;
@@ -7,6 +7,7 @@ pub trait Loadable<T> {
 pub struct ResourceBox<T: Loadable<T>> {
     resource: Option<Rc<T>>,
     path: String,
+    loaded_at: Option<SystemTime>,
 }
 
 impl<T: Loadable<T>> ResourceBox<T> {
@@ -14,6 +15,7 @@ impl<T: Loadable<T>> ResourceBox<T> {
         Self {
             resource: None,
             path: String::from(path),
+            loaded_at: None,
         }
     }
 
@@ -21,12 +23,27 @@ impl<T: Loadable<T>> ResourceBox<T> {
         if let Some(r) = &self.resource {
             return Ok(r.clone());
         }
-        let resource = Rc::new(T::load(&self.path)?.into());
-        self.resource = Some(resource);
-        return Ok(self.resource.clone().unwrap());
+        self.reload()
+    }
+
+    // Keeps the previously loaded resource if loading the new one fails
+    pub fn reload(&mut self) -> Result<Rc<T>, std::io::Error> {
+        let resource = Rc::new(T::load(&self.path)?);
+        self.resource = Some(resource.clone());
+        self.loaded_at = Some(SystemTime::now());
+        Ok(resource)
+    }
+
+    pub fn loaded(&self) -> Option<Rc<T>> {
+        self.resource.clone()
+    }
+
+    pub fn loaded_at(&self) -> Option<SystemTime> {
+        self.loaded_at
     }
 
     pub fn unload(&mut self) {
-        self.resource = None
+        self.resource = None;
+        self.loaded_at = None;
     }
 }
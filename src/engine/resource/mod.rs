@@ -1,44 +1,95 @@
+pub mod atlas;
+pub mod framealloc;
 pub mod loadable;
 pub mod meshmanager;
 pub mod model;
+pub mod pipelinecache;
+pub mod readback;
+pub mod shader;
 pub mod texmanager;
 pub mod texture;
 
 use self::{
-    loadable::*, meshmanager::MeshManager, model::ModelData, texmanager::TexManager,
+    atlas::TexAtlas,
+    framealloc::FrameAllocator,
+    loadable::*,
+    meshmanager::MeshManager,
+    model::ModelData,
+    pipelinecache::{PipelineCache, PipelineKey},
+    shader::ShaderData,
+    texmanager::TexManager,
     texture::TextureData,
 };
-use std::{collections::HashMap, num::NonZeroU32};
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
+
+use wgpu::{Device, RenderPipeline};
 
 use super::renderer::RendererState;
 
 pub struct ResourceManager {
     pub models: HashMap<String, ResourceBox<ModelData>>,
     pub textures: HashMap<String, ResourceBox<TextureData>>,
+    pub shaders: HashMap<String, ResourceBox<ShaderData>>,
+    pub reloaded_shaders: Vec<String>,
     pub mesh_manager: MeshManager,
     pub tex_manager: TexManager,
+    pub atlas: TexAtlas,
+    pub frame_alloc: FrameAllocator,
+    pub pipelines: PipelineCache,
+    pub root: &'static str,
+    last_shader_poll: Instant,
 }
 
 impl ResourceManager {
     pub fn new(root: &'static str, renderer: &RendererState) -> ResourceManager {
+        let tex_manager = TexManager::new(&renderer.device, NonZeroU32::new(256).unwrap());
+        let frame_alloc = FrameAllocator::new(&renderer.device, 1 << 20);
+        let pipelines = PipelineCache::new(
+            &renderer.device,
+            &[
+                &tex_manager.bind_group_layout,
+                &frame_alloc.bind_group_layout,
+            ],
+        );
+
         let mut manager = Self {
             models: HashMap::new(),
             textures: HashMap::new(),
+            shaders: HashMap::new(),
+            reloaded_shaders: Vec::new(),
             mesh_manager: MeshManager::new(&renderer.device, 4096),
-            tex_manager: TexManager::new(&renderer.device, NonZeroU32::new(256).unwrap()),
+            tex_manager,
+            atlas: TexAtlas::new(1024, 2, 128),
+            frame_alloc,
+            pipelines,
+            root,
+            last_shader_poll: Instant::now(),
         };
 
         manager.read_files(root, root);
         manager
     }
 
+    pub fn pipeline(&mut self, device: &Device, key: &PipelineKey) -> &RenderPipeline {
+        self.pipelines.get(
+            device,
+            &mut self.shaders,
+            &self.mesh_manager.vertex_layout,
+            key,
+        )
+    }
+
     fn read_files(&mut self, root: &str, path: &str) {
         if let Ok(dir_iter) = std::fs::read_dir(path) {
             for dir_entry in dir_iter {
                 if let Ok(entry) = dir_entry {
                     let entry_path = entry.path();
                     if entry_path.is_dir() {
-                        self.read_files(root, path);
+                        self.read_files(root, entry_path.to_str().unwrap());
                     } else {
                         let rel = pathdiff::diff_paths(&entry_path, root)
                             .unwrap()
@@ -48,10 +99,22 @@ impl ResourceManager {
                         if let Some(ext) = entry_path.extension() {
                             match ext.to_str() {
                                 Some("obj") => {
-                                    self.models.insert(rel, ResourceBox::new(path));
+                                    self.models.insert(
+                                        rel,
+                                        ResourceBox::new(entry_path.to_str().unwrap()),
+                                    );
                                 }
                                 Some("png") => {
-                                    self.textures.insert(rel, ResourceBox::new(path));
+                                    self.textures.insert(
+                                        rel,
+                                        ResourceBox::new(entry_path.to_str().unwrap()),
+                                    );
+                                }
+                                Some("wgsl") => {
+                                    self.shaders.insert(
+                                        rel,
+                                        ResourceBox::new(entry_path.to_str().unwrap()),
+                                    );
                                 }
                                 _ => (),
                             }
@@ -61,4 +124,39 @@ impl ResourceManager {
             }
         }
     }
+
+    // Reloads loaded shaders whose source or includes changed since they were loaded
+    pub fn poll_shaders(&mut self) {
+        self.reloaded_shaders.clear();
+
+        if self.last_shader_poll.elapsed() < Duration::from_millis(500) {
+            return;
+        }
+        self.last_shader_poll = Instant::now();
+
+        for (name, shader) in self.shaders.iter_mut() {
+            let (Some(data), Some(loaded_at)) = (shader.loaded(), shader.loaded_at()) else {
+                continue;
+            };
+
+            let changed = data.dependencies.iter().any(|dep| {
+                std::fs::metadata(dep)
+                    .and_then(|meta| meta.modified())
+                    .map(|modified| modified > loaded_at)
+                    .unwrap_or(false)
+            });
+
+            if changed {
+                match shader.reload() {
+                    Ok(_) => {
+                        log::info!("Reloaded shader '{name}'");
+                        self.reloaded_shaders.push(name.clone());
+                    }
+                    Err(err) => log::error!("Failed to reload shader '{name}': {err}"),
+                }
+            }
+        }
+
+        self.pipelines.invalidate(&self.reloaded_shaders);
+    }
 }
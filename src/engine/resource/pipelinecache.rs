@@ -0,0 +1,154 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use wgpu::*;
+
+use super::{loadable::ResourceBox, shader::ShaderData};
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader: String,
+    pub vs_entry: &'static str,
+    pub fs_entry: Option<&'static str>,
+    pub color_formats: Vec<TextureFormat>,
+    pub depth_format: Option<TextureFormat>,
+    pub blend: Option<BlendState>,
+    pub depth_write: bool,
+    pub depth_compare: CompareFunction,
+    pub cull_mode: Option<Face>,
+    pub topology: PrimitiveTopology,
+    pub sample_count: u32,
+}
+
+impl PipelineKey {
+    pub fn new(shader: &str, color_format: TextureFormat) -> PipelineKey {
+        Self {
+            shader: String::from(shader),
+            vs_entry: "vs_main",
+            fs_entry: Some("fs_main"),
+            color_formats: vec![color_format],
+            depth_format: None,
+            blend: Some(BlendState::REPLACE),
+            depth_write: true,
+            depth_compare: CompareFunction::Less,
+            cull_mode: Some(Face::Back),
+            topology: PrimitiveTopology::TriangleList,
+            sample_count: 1,
+        }
+    }
+}
+
+pub struct PipelineCache {
+    pub layout: PipelineLayout,
+    modules: HashMap<String, ShaderModule>,
+    pipelines: HashMap<PipelineKey, RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new(device: &Device, bind_group_layouts: &[&BindGroupLayout]) -> PipelineCache {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("PipelineCache Layout"),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            layout,
+            modules: HashMap::new(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn get(
+        &mut self,
+        device: &Device,
+        shaders: &mut HashMap<String, ResourceBox<ShaderData>>,
+        vertex_layout: &VertexBufferLayout,
+        key: &PipelineKey,
+    ) -> &RenderPipeline {
+        if !self.pipelines.contains_key(key) {
+            let pipeline = self.build(device, shaders, vertex_layout, key);
+            self.pipelines.insert(key.clone(), pipeline);
+        }
+
+        self.pipelines.get(key).unwrap()
+    }
+
+    // Pipelines built earlier in the frame, for passes that only see the resources immutably
+    pub fn cached(&self, key: &PipelineKey) -> Option<&RenderPipeline> {
+        self.pipelines.get(key)
+    }
+
+    pub fn invalidate(&mut self, shaders: &[String]) {
+        for shader in shaders {
+            self.modules.remove(shader);
+        }
+
+        self.pipelines
+            .retain(|key, _| !shaders.contains(&key.shader));
+    }
+
+    fn build(
+        &mut self,
+        device: &Device,
+        shaders: &mut HashMap<String, ResourceBox<ShaderData>>,
+        vertex_layout: &VertexBufferLayout,
+        key: &PipelineKey,
+    ) -> RenderPipeline {
+        if !self.modules.contains_key(&key.shader) {
+            let shader = shaders
+                .get_mut(&key.shader)
+                .unwrap_or_else(|| panic!("Cannot find shader at '{}'", key.shader))
+                .load()
+                .unwrap_or_else(|err| panic!("{err}"));
+
+            let module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(key.shader.as_str()),
+                source: ShaderSource::Wgsl(Cow::Owned(shader.source.clone())),
+            });
+
+            self.modules.insert(key.shader.clone(), module);
+        }
+
+        let module = self.modules.get(&key.shader).unwrap();
+
+        let targets = Vec::from_iter(key.color_formats.iter().map(|format| {
+            Some(ColorTargetState {
+                format: *format,
+                blend: key.blend,
+                write_mask: ColorWrites::ALL,
+            })
+        }));
+
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(key.shader.as_str()),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module,
+                entry_point: key.vs_entry,
+                buffers: std::slice::from_ref(vertex_layout),
+            },
+            fragment: key.fs_entry.map(|entry_point| FragmentState {
+                module,
+                entry_point,
+                targets: targets.as_slice(),
+            }),
+            primitive: PrimitiveState {
+                topology: key.topology,
+                cull_mode: key.cull_mode,
+                ..Default::default()
+            },
+            depth_stencil: key.depth_format.map(|format| DepthStencilState {
+                format,
+                depth_write_enabled: key.depth_write,
+                depth_compare: key.depth_compare,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: key.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
+}
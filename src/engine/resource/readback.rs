@@ -0,0 +1,241 @@
+use std::{
+    fmt,
+    future::Future,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use wgpu::*;
+
+use super::texture::TextureData;
+
+pub enum TexReadback {
+    RGBA8(TextureData),
+    Float {
+        width: u32,
+        height: u32,
+        layers: u32,
+        channels: u32,
+        data: Vec<f32>,
+    },
+}
+
+#[derive(Debug)]
+pub enum ReadbackError {
+    UnsupportedFormat(TextureFormat),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadbackError::UnsupportedFormat(format) => {
+                write!(f, "Cannot read back textures in format {format:?}")
+            }
+            ReadbackError::Io(err) => write!(f, "Cannot save read back texture: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+impl From<std::io::Error> for ReadbackError {
+    fn from(err: std::io::Error) -> Self {
+        ReadbackError::Io(err)
+    }
+}
+
+#[derive(Default)]
+struct ReadbackState {
+    result: Option<Result<(), BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+pub struct TexReadbackFuture {
+    buffer: Buffer,
+    state: Arc<Mutex<ReadbackState>>,
+    format: TextureFormat,
+    size: Extent3d,
+    row_bytes: u32,
+    padded_row_bytes: u32,
+}
+
+pub fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    format: TextureFormat,
+    size: Extent3d,
+) -> Result<TexReadbackFuture, ReadbackError> {
+    if !is_readable(format) {
+        return Err(ReadbackError::UnsupportedFormat(format));
+    }
+
+    let info = format.describe();
+
+    let row_bytes = info.block_size as u32 * size.width;
+    let padded_row_bytes = row_bytes.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Texture Readback"),
+        size: (padded_row_bytes * size.height * size.depth_or_array_layers) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Texture Readback"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: if let TextureSampleType::Depth = info.sample_type {
+                TextureAspect::DepthOnly
+            } else {
+                TextureAspect::All
+            },
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                rows_per_image: NonZeroU32::new(size.height),
+            },
+        },
+        size,
+    );
+
+    queue.submit(Some(encoder.finish()));
+
+    Ok(TexReadbackFuture::new(
+        buffer,
+        format,
+        size,
+        row_bytes,
+        padded_row_bytes,
+    ))
+}
+
+fn is_readable(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb
+            | TextureFormat::Rgba32Float
+            | TextureFormat::Depth32Float
+    )
+}
+
+impl TexReadbackFuture {
+    fn new(
+        buffer: Buffer,
+        format: TextureFormat,
+        size: Extent3d,
+        row_bytes: u32,
+        padded_row_bytes: u32,
+    ) -> TexReadbackFuture {
+        let state = Arc::new(Mutex::new(ReadbackState::default()));
+        let callback_state = state.clone();
+
+        buffer.slice(..).map_async(MapMode::Read, move |result| {
+            let mut state = callback_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        Self {
+            buffer,
+            state,
+            format,
+            size,
+            row_bytes,
+            padded_row_bytes,
+        }
+    }
+
+    pub fn wait(self, device: &Device) -> TexReadback {
+        device.poll(Maintain::Wait);
+        pollster::block_on(self)
+    }
+
+    fn read_mapped(&self) -> TexReadback {
+        let mapped = self.buffer.slice(..).get_mapped_range();
+
+        let mut bytes = Vec::with_capacity(
+            (self.row_bytes * self.size.height * self.size.depth_or_array_layers) as usize,
+        );
+        for row in mapped.chunks_exact(self.padded_row_bytes as usize) {
+            bytes.extend_from_slice(&row[..self.row_bytes as usize]);
+        }
+
+        drop(mapped);
+        self.buffer.unmap();
+
+        let width = self.size.width;
+        let height = self.size.height;
+        let layers = self.size.depth_or_array_layers;
+
+        match self.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                TexReadback::RGBA8(TextureData {
+                    width,
+                    height: height * layers,
+                    data: bytes,
+                })
+            }
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                for pixel in bytes.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+
+                TexReadback::RGBA8(TextureData {
+                    width,
+                    height: height * layers,
+                    data: bytes,
+                })
+            }
+            TextureFormat::Rgba32Float | TextureFormat::Depth32Float => TexReadback::Float {
+                width,
+                height,
+                layers,
+                channels: self.format.describe().components as u32,
+                data: Vec::from_iter(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|value| f32::from_ne_bytes(value.try_into().unwrap())),
+                ),
+            },
+            _ => unreachable!("Readback format was checked in read_texture"),
+        }
+    }
+}
+
+impl Future for TexReadbackFuture {
+    type Output = TexReadback;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<TexReadback> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.result.take() {
+            Some(Ok(())) => {
+                drop(state);
+                Poll::Ready(self.read_mapped())
+            }
+            Some(Err(err)) => panic!("Texture readback failed: {err}"),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
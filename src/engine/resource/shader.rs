@@ -0,0 +1,184 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use naga::{
+    valid::{Capabilities, ValidationFlags, Validator},
+    Module,
+};
+
+use super::loadable::Loadable;
+
+pub struct ShaderData {
+    pub source: String,
+    pub module: Module,
+    pub dependencies: Vec<PathBuf>,
+}
+
+impl Loadable<ShaderData> for ShaderData {
+    fn load(path: &str) -> Result<ShaderData, std::io::Error> {
+        let mut preprocessor = Preprocessor::default();
+        preprocessor.process_file(Path::new(path))?;
+
+        let source = preprocessor.output;
+        let line_map = preprocessor.line_map;
+
+        let error_at = |line: Option<u32>, message: String| {
+            let origin = line
+                .and_then(|line| line_map.get(line as usize - 1))
+                .map(|(file, line)| format!("{}:{}", file.display(), line))
+                .unwrap_or(String::from(path));
+
+            Error::new(ErrorKind::InvalidData, format!("{origin}: {message}"))
+        };
+
+        let module = naga::front::wgsl::parse_str(&source).map_err(|err| {
+            error_at(
+                err.location(&source).map(|loc| loc.line_number),
+                err.message().to_string(),
+            )
+        })?;
+
+        Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|err| {
+                error_at(
+                    err.location(&source).map(|loc| loc.line_number),
+                    err.as_inner().to_string(),
+                )
+            })?;
+
+        Ok(ShaderData {
+            source,
+            module,
+            dependencies: Vec::from_iter(preprocessor.included),
+        })
+    }
+}
+
+// Handles `#include "file"` (resolved relative to the including file, each file once),
+// `#define NAME value` token substitution and `#ifdef`/`#ifndef`/`#else`/`#endif`
+#[derive(Default)]
+struct Preprocessor {
+    output: String,
+    line_map: Vec<(PathBuf, usize)>,
+    defines: HashMap<String, String>,
+    included: HashSet<PathBuf>,
+}
+
+impl Preprocessor {
+    fn process_file(&mut self, path: &Path) -> Result<(), Error> {
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        if !self.included.insert(path.clone()) {
+            return Ok(());
+        }
+
+        let source = std::fs::read_to_string(&path)?;
+        let mut active = vec![true];
+
+        for (idx, line) in source.lines().enumerate() {
+            let line_number = idx + 1;
+            let trimmed = line.trim_start();
+            let error = |message: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}:{}: {}", path.display(), line_number, message),
+                )
+            };
+
+            if let Some(directive) = trimmed.strip_prefix('#') {
+                let mut parts = directive.splitn(2, char::is_whitespace);
+                let name = parts.next().unwrap_or("");
+                let args = parts.next().unwrap_or("").trim();
+                let enabled = active.iter().all(|&a| a);
+
+                match name {
+                    "ifdef" => active.push(self.defines.contains_key(args)),
+                    "ifndef" => active.push(!self.defines.contains_key(args)),
+                    "else" => {
+                        if active.len() < 2 {
+                            return Err(error("#else without #ifdef"));
+                        }
+                        let last = active.pop().unwrap();
+                        active.push(!last);
+                    }
+                    "endif" => {
+                        if active.len() < 2 {
+                            return Err(error("#endif without #ifdef"));
+                        }
+                        active.pop();
+                    }
+                    "define" if enabled => {
+                        let mut define = args.splitn(2, char::is_whitespace);
+                        let key = define.next().unwrap_or("");
+                        if key.is_empty() {
+                            return Err(error("#define without a name"));
+                        }
+                        self.defines.insert(
+                            key.to_string(),
+                            define.next().unwrap_or("").trim().to_string(),
+                        );
+                    }
+                    "include" if enabled => {
+                        let file = args.trim_matches('"');
+                        let include = path.parent().unwrap().join(file);
+                        if !include.exists() {
+                            return Err(error(&format!("cannot find include '{file}'")));
+                        }
+                        self.process_file(&include)?;
+                    }
+                    "define" | "include" => {}
+                    _ => return Err(error(&format!("unknown directive '#{name}'"))),
+                }
+
+                continue;
+            }
+
+            if active.iter().all(|&a| a) {
+                let expanded = self.expand(line);
+                self.output.push_str(&expanded);
+                self.output.push('\n');
+                self.line_map.push((path.clone(), line_number));
+            }
+        }
+
+        if active.len() != 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: unterminated #ifdef", path.display()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn expand(&self, line: &str) -> String {
+        if self.defines.is_empty() {
+            return line.to_string();
+        }
+
+        let mut result = String::with_capacity(line.len());
+        let mut token = String::new();
+
+        for c in line.chars().chain(std::iter::once('\n')) {
+            if c.is_alphanumeric() || c == '_' {
+                token.push(c);
+                continue;
+            }
+
+            match self.defines.get(&token) {
+                Some(value) => result.push_str(value),
+                None => result.push_str(&token),
+            }
+            token.clear();
+
+            if c != '\n' {
+                result.push(c);
+            }
+        }
+
+        result
+    }
+}
@@ -1,24 +1,232 @@
-use std::{collections::HashMap, num::NonZeroU32};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    num::NonZeroU32,
+};
 
 use wgpu::{util::DeviceExt, *};
 
-use super::texture::TextureData;
+use super::{
+    readback::{read_texture, ReadbackError, TexReadbackFuture},
+    texture::TextureData,
+};
 
 pub struct TexHandle(u64);
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TexId(u64);
+
+impl TexHandle {
+    pub fn id(&self) -> TexId {
+        TexId(self.0)
+    }
+}
+
 pub enum TexDataFormat<'a> {
     StaticRGBA8(&'a TextureData),
+    StaticRGBA8Array(&'a [&'a TextureData]),
+    StaticRGBA8Cube([&'a TextureData; 6]),
+    StaticRGBA8Volume(&'a TextureData, u32),
     DynamicRGBA32(u32, u32),
+    DynamicRGBA32Array(u32, u32, u32),
+    DynamicRGBA32Cube(u32),
+    DynamicRGBA32Volume(u32, u32, u32),
     DynamicDepth(u32, u32),
+    DynamicDepthCube(u32),
+}
+
+#[derive(Debug)]
+pub enum TexError {
+    EmptyArray,
+    LayerSizeMismatch,
+    UnevenVolume { height: u32, depth: u32 },
+    NotACross { width: u32, height: u32 },
+    RegionOutOfBounds,
+    RegionSizeMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for TexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TexError::EmptyArray => write!(f, "Texture arrays need at least one layer"),
+            TexError::LayerSizeMismatch => write!(f, "Texture layers must all have the same size"),
+            TexError::UnevenVolume { height, depth } => write!(
+                f,
+                "Volume image height {height} is not a multiple of its depth {depth}"
+            ),
+            TexError::NotACross { width, height } => write!(
+                f,
+                "Cubemap cross of {width}x{height} must have a 4x3 or 3x4 aspect ratio"
+            ),
+            TexError::RegionOutOfBounds => write!(f, "Texture region is out of bounds"),
+            TexError::RegionSizeMismatch { expected, actual } => write!(
+                f,
+                "Texture region needs {expected} bytes of data but got {actual}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TexError {}
+
+pub struct TexRegion {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+}
+
+impl TexRegion {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> TexRegion {
+        Self {
+            x,
+            y,
+            z: 0,
+            width,
+            height,
+            depth: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TexKind {
+    D2,
+    D2Array,
+    Cube,
+    D3,
+    Depth,
+    DepthCube,
+}
+
+impl TexKind {
+    const ALL: [TexKind; 6] = [
+        TexKind::D2,
+        TexKind::D2Array,
+        TexKind::Cube,
+        TexKind::D3,
+        TexKind::Depth,
+        TexKind::DepthCube,
+    ];
+
+    fn binding(&self) -> u32 {
+        match self {
+            TexKind::D2 => 0,
+            TexKind::D2Array => 3,
+            TexKind::Cube => 4,
+            TexKind::D3 => 5,
+            TexKind::Depth => 6,
+            TexKind::DepthCube => 7,
+        }
+    }
+
+    fn view_dimension(&self) -> TextureViewDimension {
+        match self {
+            TexKind::D2 | TexKind::Depth => TextureViewDimension::D2,
+            TexKind::D2Array => TextureViewDimension::D2Array,
+            TexKind::Cube | TexKind::DepthCube => TextureViewDimension::Cube,
+            TexKind::D3 => TextureViewDimension::D3,
+        }
+    }
+
+    // Depth textures get their own tables, they can't be bound where filterable floats are expected
+    fn sample_type(&self) -> TextureSampleType {
+        match self {
+            TexKind::Depth | TexKind::DepthCube => TextureSampleType::Depth,
+            _ => TextureSampleType::Float { filterable: true },
+        }
+    }
+
+    fn placeholder_format(&self) -> TextureFormat {
+        match self {
+            TexKind::Depth | TexKind::DepthCube => TextureFormat::Depth32Float,
+            _ => TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    fn table(&self) -> usize {
+        *self as usize
+    }
+}
+
+struct TexSlot {
+    texture: Texture,
+    view: TextureView,
+    attachment_view: Option<TextureView>,
+    format: TextureFormat,
+    size: Extent3d,
+}
+
+struct TexTable {
+    slots: Vec<Option<TexSlot>>,
+    free_slots: Vec<usize>,
+    placeholder: TextureView,
+}
+
+impl TexTable {
+    fn new(device: &Device, kind: TexKind) -> TexTable {
+        let placeholder = device.create_texture(&TextureDescriptor {
+            label: Some("TexManager Placeholder"),
+            size: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: if let TexKind::Cube | TexKind::DepthCube = kind {
+                    6
+                } else {
+                    1
+                },
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: if let TexKind::D3 = kind {
+                TextureDimension::D3
+            } else {
+                TextureDimension::D2
+            },
+            format: kind.placeholder_format(),
+            usage: TextureUsages::TEXTURE_BINDING,
+        });
+
+        Self {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            placeholder: placeholder.create_view(&TextureViewDescriptor {
+                dimension: Some(kind.view_dimension()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn take_slot(&mut self, page_size: usize) -> usize {
+        if self.free_slots.is_empty() {
+            let start = self.slots.len();
+            self.slots.extend((0..page_size).map(|_| None));
+            self.free_slots.extend((start..start + page_size).rev());
+        }
+
+        self.free_slots.pop().unwrap()
+    }
+
+    // Empty slots are bound as placeholders so every page is fully bound
+    fn page_views(&self, page: usize, page_size: usize) -> Vec<&TextureView> {
+        Vec::from_iter((page * page_size..(page + 1) * page_size).map(|idx| {
+            match self.slots.get(idx) {
+                Some(Some(slot)) => &slot.view,
+                _ => &self.placeholder,
+            }
+        }))
+    }
 }
 
 pub struct TexManager {
-    committed: Vec<(Texture, TextureView)>,
-    mapping_ids: Vec<u64>,
+    tables: Vec<TexTable>,
     pub bind_group_layout: BindGroupLayout,
-    pub bind_group: BindGroup,
-    max_size: usize,
-    alloc_mapping: HashMap<u64, usize>,
+    pages: Vec<BindGroup>,
+    dirty_pages: HashSet<usize>,
+    page_size: usize,
+    alloc_mapping: HashMap<u64, (TexKind, usize)>,
     id_count: u64,
     bilinear: Sampler,
     nearest: Sampler,
@@ -56,64 +264,53 @@ impl TexManager {
             border_color: None,
         });
 
-        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("TexManager Layout"),
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Texture {
-                        sample_type: TextureSampleType::Float { filterable: true },
-                        view_dimension: TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: Some(size),
-                },
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+        let mut entries = vec![
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
 
-        let group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("TexManager Group"),
-            layout: &layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureViewArray(&[]),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&bilinear),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::Sampler(&nearest),
+        for kind in TexKind::ALL {
+            entries.push(BindGroupLayoutEntry {
+                binding: kind.binding(),
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: kind.sample_type(),
+                    view_dimension: kind.view_dimension(),
+                    multisampled: false,
                 },
-            ],
+                count: Some(size),
+            });
+        }
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("TexManager Layout"),
+            entries: entries.as_slice(),
         });
 
-        TexManager {
-            committed: Vec::new(),
+        let mut manager = TexManager {
+            tables: Vec::from_iter(TexKind::ALL.iter().map(|kind| TexTable::new(device, *kind))),
             bind_group_layout: layout,
-            bind_group: group,
+            pages: Vec::new(),
+            dirty_pages: HashSet::from([0]),
             alloc_mapping: HashMap::new(),
             id_count: 0,
             bilinear,
             nearest,
-            mapping_ids: Vec::new(),
-            max_size: size.get() as usize,
-        }
+            page_size: size.get() as usize,
+        };
+
+        manager.flush(device);
+        manager
     }
 
     pub fn alloc_tex(
@@ -121,123 +318,466 @@ impl TexManager {
         device: &Device,
         queue: &Queue,
         tex_data: TexDataFormat,
-    ) -> TexHandle {
-        if self.max_size == self.committed.len() {
-            panic!("Unable to allocate texture!");
-        }
+    ) -> Result<TexHandle, TexError> {
+        Self::validate(&tex_data)?;
+
+        let (w, h, layers, format, kind) = match tex_data {
+            TexDataFormat::StaticRGBA8(data) => (
+                data.width,
+                data.height,
+                1,
+                TextureFormat::Rgba8Unorm,
+                TexKind::D2,
+            ),
+            TexDataFormat::StaticRGBA8Array(data) => (
+                data[0].width,
+                data[0].height,
+                data.len() as u32,
+                TextureFormat::Rgba8Unorm,
+                TexKind::D2Array,
+            ),
+            TexDataFormat::StaticRGBA8Cube(data) => (
+                data[0].width,
+                data[0].height,
+                6,
+                TextureFormat::Rgba8Unorm,
+                TexKind::Cube,
+            ),
+            TexDataFormat::StaticRGBA8Volume(data, depth) => (
+                data.width,
+                data.height / depth,
+                depth,
+                TextureFormat::Rgba8Unorm,
+                TexKind::D3,
+            ),
 
-        let (w, h, format) = match tex_data {
-            TexDataFormat::StaticRGBA8(data) => {
-                (data.width, data.height, TextureFormat::Rgba8Unorm)
+            TexDataFormat::DynamicRGBA32(w, h) => {
+                (w, h, 1, TextureFormat::Rgba32Float, TexKind::D2)
+            }
+            TexDataFormat::DynamicRGBA32Array(w, h, layers) => {
+                (w, h, layers, TextureFormat::Rgba32Float, TexKind::D2Array)
+            }
+            TexDataFormat::DynamicRGBA32Cube(size) => {
+                (size, size, 6, TextureFormat::Rgba32Float, TexKind::Cube)
+            }
+            TexDataFormat::DynamicRGBA32Volume(w, h, d) => {
+                (w, h, d, TextureFormat::Rgba32Float, TexKind::D3)
             }
+            TexDataFormat::DynamicDepth(w, h) => {
+                (w, h, 1, TextureFormat::Depth32Float, TexKind::Depth)
+            }
+            TexDataFormat::DynamicDepthCube(size) => (
+                size,
+                size,
+                6,
+                TextureFormat::Depth32Float,
+                TexKind::DepthCube,
+            ),
+        };
+
+        let size = Extent3d {
+            width: w,
+            height: h,
+            depth_or_array_layers: layers,
+        };
 
-            TexDataFormat::DynamicRGBA32(w, h) => (w, h, TextureFormat::Rgba32Float),
-            TexDataFormat::DynamicDepth(w, h) => (w, h, TextureFormat::Depth24Plus),
+        let render_usage = match tex_data {
+            TexDataFormat::DynamicRGBA32(..)
+            | TexDataFormat::DynamicRGBA32Array(..)
+            | TexDataFormat::DynamicRGBA32Cube(..)
+            | TexDataFormat::DynamicDepth(..)
+            | TexDataFormat::DynamicDepthCube(..) => TextureUsages::RENDER_ATTACHMENT,
+            _ => TextureUsages::empty(),
         };
 
         let desc = TextureDescriptor {
             label: None,
-            size: Extent3d {
-                width: w,
-                height: h,
-                depth_or_array_layers: 1,
-            },
+            size,
             mip_level_count: 1,
             sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: format,
-            usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+            dimension: if let TexKind::D3 = kind {
+                TextureDimension::D3
+            } else {
+                TextureDimension::D2
+            },
+            format,
+            usage: TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST
+                | TextureUsages::TEXTURE_BINDING
+                | render_usage,
         };
 
-        let tex = if let TexDataFormat::StaticRGBA8(data) = tex_data {
-            device.create_texture_with_data(queue, &desc, data.data.as_slice())
-        } else {
-            device.create_texture(&desc)
+        let tex = match tex_data {
+            TexDataFormat::StaticRGBA8(data) | TexDataFormat::StaticRGBA8Volume(data, _) => {
+                device.create_texture_with_data(queue, &desc, data.data.as_slice())
+            }
+            TexDataFormat::StaticRGBA8Array(data) => {
+                let layers = Self::concat_layers(w, h, data);
+                device.create_texture_with_data(queue, &desc, layers.as_slice())
+            }
+            TexDataFormat::StaticRGBA8Cube(data) => {
+                let layers = Self::concat_layers(w, h, &data);
+                device.create_texture_with_data(queue, &desc, layers.as_slice())
+            }
+            _ => device.create_texture(&desc),
         };
 
-        let view = tex.create_view(&TextureViewDescriptor::default());
+        let view = tex.create_view(&TextureViewDescriptor {
+            dimension: Some(kind.view_dimension()),
+            ..Default::default()
+        });
+
+        // Render passes can only attach a single 2D layer, so cube and array targets draw into layer 0
+        let attachment_view = (!render_usage.is_empty()).then(|| {
+            tex.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: 0,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            })
+        });
 
-        self.committed.push((tex, view));
-        self.mapping_ids.push(self.id_count);
+        let table = &mut self.tables[kind.table()];
+        let idx = table.take_slot(self.page_size);
+        table.slots[idx] = Some(TexSlot {
+            texture: tex,
+            view,
+            attachment_view,
+            format,
+            size,
+        });
 
-        self.rebuild_binds(device);
+        self.dirty_pages.insert(idx / self.page_size);
 
         let handle = TexHandle(self.id_count);
 
-        self.alloc_mapping
-            .insert(self.id_count, self.committed.len() - 1);
+        self.alloc_mapping.insert(self.id_count, (kind, idx));
 
         self.id_count += 1;
 
-        handle
+        Ok(handle)
+    }
+
+    // Checked before anything is created so a bad description never leaves a half made texture
+    fn validate(tex_data: &TexDataFormat) -> Result<(), TexError> {
+        let same_size = |layers: &[&TextureData]| {
+            layers
+                .windows(2)
+                .all(|pair| pair[0].width == pair[1].width && pair[0].height == pair[1].height)
+        };
+
+        match *tex_data {
+            TexDataFormat::StaticRGBA8Array([]) => Err(TexError::EmptyArray),
+            TexDataFormat::StaticRGBA8Array(layers) if !same_size(layers) => {
+                Err(TexError::LayerSizeMismatch)
+            }
+            TexDataFormat::StaticRGBA8Cube(ref faces) if !same_size(faces) => {
+                Err(TexError::LayerSizeMismatch)
+            }
+            TexDataFormat::StaticRGBA8Volume(data, depth)
+                if depth == 0 || data.height % depth != 0 =>
+            {
+                Err(TexError::UnevenVolume {
+                    height: data.height,
+                    depth,
+                })
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn free_tex(&mut self, alloc: TexHandle) {
-        let id = alloc.0;
-        let idx = *self.alloc_mapping.get(&id).unwrap();
-        let remap_id = self.mapping_ids.swap_remove(idx);
+        let (kind, idx) = self.alloc_mapping.remove(&alloc.0).unwrap();
+        let table = &mut self.tables[kind.table()];
 
-        self.committed.swap_remove(idx);
+        table.slots[idx] = None;
+        table.free_slots.push(idx);
 
-        self.alloc_mapping.remove(&id);
-        self.alloc_mapping.insert(remap_id, idx);
+        self.dirty_pages.insert(idx / self.page_size);
     }
 
-    pub fn set_data(&mut self, queue: &Queue, alloc: &TexHandle, tex_data: &TextureData) {
-        let idx = *self.alloc_mapping.get(&alloc.0).unwrap();
-        let tex = self.committed.get(idx).unwrap();
+    pub fn set_data(
+        &mut self,
+        queue: &Queue,
+        alloc: &TexHandle,
+        tex_data: &TextureData,
+    ) -> Result<(), TexError> {
+        self.write_region(
+            queue,
+            alloc,
+            &TexRegion::new(0, 0, tex_data.width, tex_data.height),
+            &tex_data.data,
+        )
+    }
+
+    pub fn write_region(
+        &mut self,
+        queue: &Queue,
+        alloc: &TexHandle,
+        region: &TexRegion,
+        data: &[u8],
+    ) -> Result<(), TexError> {
+        let (kind, idx) = *self.alloc_mapping.get(&alloc.0).unwrap();
+        let tex = self.tables[kind.table()].slots[idx].as_ref().unwrap();
+
+        let block_size = tex.format.describe().block_size as u32;
+        Self::check_region(tex.size, block_size, region, data.len())?;
+
+        let row_bytes = block_size * region.width;
+        let padded_row_bytes = row_bytes.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let padded;
+        let data = if padded_row_bytes == row_bytes {
+            data
+        } else {
+            padded = Self::pad_rows(data, row_bytes as usize, padded_row_bytes as usize);
+            padded.as_slice()
+        };
 
         queue.write_texture(
             ImageCopyTexture {
-                texture: &tex.0,
+                texture: &tex.texture,
                 mip_level: 0,
-                origin: Origin3d::ZERO,
+                origin: Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: region.z,
+                },
                 aspect: TextureAspect::All,
             },
-            &tex_data.data,
+            data,
             ImageDataLayout {
                 offset: 0,
-                bytes_per_row: NonZeroU32::new(4 * tex_data.width as u32),
-                rows_per_image: NonZeroU32::new(tex_data.height as u32),
+                bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                rows_per_image: NonZeroU32::new(region.height),
             },
             Extent3d {
-                width: tex_data.width as u32,
-                height: tex_data.height as u32,
-                depth_or_array_layers: 1,
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: region.depth,
             },
-        )
+        );
+        Ok(())
+    }
+
+    fn check_region(
+        size: Extent3d,
+        block_size: u32,
+        region: &TexRegion,
+        data_len: usize,
+    ) -> Result<(), TexError> {
+        let fits =
+            |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+        if !fits(region.x, region.width, size.width)
+            || !fits(region.y, region.height, size.height)
+            || !fits(region.z, region.depth, size.depth_or_array_layers)
+        {
+            return Err(TexError::RegionOutOfBounds);
+        }
+
+        let expected = (block_size * region.width * region.height * region.depth) as usize;
+        if data_len != expected {
+            return Err(TexError::RegionSizeMismatch {
+                expected,
+                actual: data_len,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn read_tex(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        alloc: &TexHandle,
+    ) -> Result<TexReadbackFuture, ReadbackError> {
+        let (kind, idx) = *self.alloc_mapping.get(&alloc.0).unwrap();
+        let tex = self.tables[kind.table()].slots[idx].as_ref().unwrap();
+
+        read_texture(device, queue, &tex.texture, tex.format, tex.size)
     }
 
     pub fn get_index(&self, handle: &TexHandle) -> usize {
-        *self.alloc_mapping.get(&handle.0).unwrap()
+        self.alloc_mapping.get(&handle.0).unwrap().1 % self.page_size
+    }
+
+    pub fn get_page(&self, handle: &TexHandle) -> usize {
+        self.alloc_mapping.get(&handle.0).unwrap().1 / self.page_size
+    }
+
+    pub fn bind_group(&self, page: usize) -> &BindGroup {
+        &self.pages[page]
     }
 
-    fn rebuild_binds(&mut self, device: &Device) {
-        let group = device.create_bind_group(&BindGroupDescriptor {
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn get_kind(&self, handle: &TexHandle) -> TexKind {
+        self.alloc_mapping.get(&handle.0).unwrap().0
+    }
+
+    pub fn get_view(&self, id: TexId) -> &TextureView {
+        &self.get_slot(id).view
+    }
+
+    pub fn get_attachment_view(&self, id: TexId) -> &TextureView {
+        self.get_slot(id)
+            .attachment_view
+            .as_ref()
+            .expect("Texture was not allocated as a render target")
+    }
+
+    pub fn get_format(&self, id: TexId) -> TextureFormat {
+        self.get_slot(id).format
+    }
+
+    pub fn get_size(&self, id: TexId) -> Extent3d {
+        self.get_slot(id).size
+    }
+
+    fn get_slot(&self, id: TexId) -> &TexSlot {
+        let (kind, idx) = *self.alloc_mapping.get(&id.0).unwrap();
+        self.tables[kind.table()].slots[idx].as_ref().unwrap()
+    }
+
+    fn pad_rows(data: &[u8], row_bytes: usize, padded_row_bytes: usize) -> Vec<u8> {
+        let mut padded = vec![0; padded_row_bytes * (data.len() / row_bytes)];
+        for (src, dst) in data
+            .chunks_exact(row_bytes)
+            .zip(padded.chunks_exact_mut(padded_row_bytes))
+        {
+            dst[..row_bytes].copy_from_slice(src);
+        }
+        padded
+    }
+
+    fn concat_layers(width: u32, height: u32, layers: &[&TextureData]) -> Vec<u8> {
+        let mut data = Vec::with_capacity((4 * width * height) as usize * layers.len());
+        for layer in layers {
+            data.extend_from_slice(layer.data.as_slice());
+        }
+        data
+    }
+
+    pub fn flush(&mut self, device: &Device) {
+        let page_count = self
+            .tables
+            .iter()
+            .map(|table| table.slots.len() / self.page_size)
+            .max()
+            .unwrap()
+            .max(1);
+
+        self.dirty_pages.extend(self.pages.len()..page_count);
+
+        let mut dirty = Vec::from_iter(self.dirty_pages.drain());
+        dirty.sort();
+
+        for page in dirty {
+            let group = self.build_page(device, page);
+
+            if page < self.pages.len() {
+                self.pages[page] = group;
+            } else {
+                self.pages.push(group);
+            }
+        }
+    }
+
+    fn build_page(&self, device: &Device, page: usize) -> BindGroup {
+        let views = Vec::from_iter(
+            self.tables
+                .iter()
+                .map(|table| table.page_views(page, self.page_size)),
+        );
+
+        let mut entries = vec![
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(&self.bilinear),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(&self.nearest),
+            },
+        ];
+
+        for kind in TexKind::ALL {
+            entries.push(BindGroupEntry {
+                binding: kind.binding(),
+                resource: BindingResource::TextureViewArray(views[kind.table()].as_slice()),
+            });
+        }
+
+        device.create_bind_group(&BindGroupDescriptor {
             label: Some("TexManager Group"),
             layout: &self.bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureViewArray(
-                        self.committed
-                            .iter()
-                            .map(|view| &view.1)
-                            .collect::<Vec<_>>()
-                            .as_slice(),
-                    ),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&self.bilinear),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::Sampler(&self.nearest),
-                },
-            ],
-        });
+            entries: entries.as_slice(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32) -> TextureData {
+        TextureData {
+            width,
+            height,
+            data: vec![0; (4 * width * height) as usize],
+        }
+    }
+
+    #[test]
+    fn arrays_need_layers_of_one_size() {
+        let (a, b) = (image(4, 4), image(8, 4));
+        assert!(matches!(
+            TexManager::validate(&TexDataFormat::StaticRGBA8Array(&[])),
+            Err(TexError::EmptyArray)
+        ));
+        assert!(matches!(
+            TexManager::validate(&TexDataFormat::StaticRGBA8Array(&[&a, &b])),
+            Err(TexError::LayerSizeMismatch)
+        ));
+        assert!(TexManager::validate(&TexDataFormat::StaticRGBA8Array(&[&a, &a])).is_ok());
+    }
+
+    #[test]
+    fn volumes_need_whole_slices() {
+        let data = image(4, 12);
+        assert!(TexManager::validate(&TexDataFormat::StaticRGBA8Volume(&data, 3)).is_ok());
+        assert!(matches!(
+            TexManager::validate(&TexDataFormat::StaticRGBA8Volume(&data, 5)),
+            Err(TexError::UnevenVolume { .. })
+        ));
+        assert!(TexManager::validate(&TexDataFormat::StaticRGBA8Volume(&data, 0)).is_err());
+    }
+
+    #[test]
+    fn regions_must_fit_and_match_their_data() {
+        let size = Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        };
+        let check = |region: TexRegion, len: usize| TexManager::check_region(size, 4, &region, len);
 
-        self.bind_group = group;
+        assert!(check(TexRegion::new(2, 2, 2, 2), 16).is_ok());
+        assert!(matches!(
+            check(TexRegion::new(3, 0, 2, 1), 8),
+            Err(TexError::RegionOutOfBounds)
+        ));
+        assert!(matches!(
+            check(TexRegion::new(u32::MAX, 0, 2, 1), 8),
+            Err(TexError::RegionOutOfBounds)
+        ));
+        assert!(matches!(
+            check(TexRegion::new(0, 0, 2, 2), 12),
+            Err(TexError::RegionSizeMismatch {
+                expected: 16,
+                actual: 12
+            })
+        ));
     }
 }
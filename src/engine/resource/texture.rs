@@ -1,6 +1,6 @@
 use std::{fs::File, io::BufReader};
 
-use super::loadable::Loadable;
+use super::{loadable::Loadable, texmanager::TexError};
 
 pub struct TextureData {
     pub width: u32,
@@ -19,3 +19,101 @@ impl Loadable<TextureData> for TextureData {
         })
     }
 }
+
+impl TextureData {
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> TextureData {
+        let mut data = Vec::with_capacity((4 * width * height) as usize);
+        for row in y..y + height {
+            let start = (4 * (row * self.width + x)) as usize;
+            data.extend_from_slice(&self.data[start..start + 4 * width as usize]);
+        }
+
+        TextureData {
+            width,
+            height,
+            data,
+        }
+    }
+
+    // Splits a horizontal (4x3) or vertical (3x4) cross into +X, -X, +Y, -Y, +Z, -Z faces
+    pub fn cross_faces(&self) -> Result<[TextureData; 6], TexError> {
+        if self.width == 0 {
+            Err(TexError::NotACross {
+                width: self.width,
+                height: self.height,
+            })
+        } else if self.width * 3 == self.height * 4 {
+            let size = self.width / 4;
+            Ok([
+                self.crop(2 * size, size, size, size),
+                self.crop(0, size, size, size),
+                self.crop(size, 0, size, size),
+                self.crop(size, 2 * size, size, size),
+                self.crop(size, size, size, size),
+                self.crop(3 * size, size, size, size),
+            ])
+        } else if self.width * 4 == self.height * 3 {
+            let size = self.width / 3;
+            let back = self.crop(size, 3 * size, size, size);
+            Ok([
+                self.crop(2 * size, size, size, size),
+                self.crop(0, size, size, size),
+                self.crop(size, 0, size, size),
+                self.crop(size, 2 * size, size, size),
+                self.crop(size, size, size, size),
+                back.rotated_180(),
+            ])
+        } else {
+            Err(TexError::NotACross {
+                width: self.width,
+                height: self.height,
+            })
+        }
+    }
+
+    fn rotated_180(&self) -> TextureData {
+        let mut data = Vec::with_capacity(self.data.len());
+        for pixel in self.data.chunks_exact(4).rev() {
+            data.extend_from_slice(pixel);
+        }
+
+        TextureData {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32) -> TextureData {
+        TextureData {
+            width,
+            height,
+            data: vec![0; (4 * width * height) as usize],
+        }
+    }
+
+    #[test]
+    fn crosses_split_into_square_faces() {
+        for cross in [image(8, 6), image(6, 8)] {
+            let faces = cross.cross_faces().unwrap();
+            assert!(faces.iter().all(|face| face.width == 2 && face.height == 2));
+        }
+    }
+
+    #[test]
+    fn other_aspect_ratios_are_not_crosses() {
+        assert!(matches!(
+            image(8, 8).cross_faces(),
+            Err(TexError::NotACross {
+                width: 8,
+                height: 8
+            })
+        ));
+        assert!(image(0, 0).cross_faces().is_err());
+    }
+}
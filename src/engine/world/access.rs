@@ -0,0 +1,208 @@
+use std::any::{type_name, TypeId};
+
+use parking_lot::{MappedRwLockReadGuard, MappedRwLockWriteGuard};
+
+use super::{
+    commands::Commands,
+    component::{Component, SparseSet},
+    events::Events,
+    id::EntityId,
+    tags::TagQuery,
+    EntityTransform, World,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AccessKey {
+    Component(TypeId),
+    Resource(TypeId),
+    Event(TypeId),
+    Tag(String),
+    Transforms,
+}
+
+// Behaviors that declare access run on the thread pool next to any behavior they don't conflict with
+#[derive(Clone, Default, Debug)]
+pub struct Access {
+    pub reads: Vec<AccessKey>,
+    pub writes: Vec<AccessKey>,
+}
+
+impl Access {
+    pub fn new() -> Access {
+        Self::default()
+    }
+
+    pub fn read<T: Component>(mut self) -> Access {
+        self.reads.push(AccessKey::Component(TypeId::of::<T>()));
+        self
+    }
+
+    pub fn write<T: Component>(mut self) -> Access {
+        self.writes.push(AccessKey::Component(TypeId::of::<T>()));
+        self
+    }
+
+    pub fn read_resource<T: Component>(mut self) -> Access {
+        self.reads.push(AccessKey::Resource(TypeId::of::<T>()));
+        self
+    }
+
+    pub fn write_resource<T: Component>(mut self) -> Access {
+        self.writes.push(AccessKey::Resource(TypeId::of::<T>()));
+        self
+    }
+
+    pub fn read_events<E: Component>(mut self) -> Access {
+        self.reads.push(AccessKey::Event(TypeId::of::<E>()));
+        self
+    }
+
+    pub fn send_events<E: Component>(mut self) -> Access {
+        self.writes.push(AccessKey::Event(TypeId::of::<E>()));
+        self
+    }
+
+    pub fn read_tag(mut self, tag: &str) -> Access {
+        self.reads.push(AccessKey::Tag(String::from(tag)));
+        self
+    }
+
+    pub fn read_transforms(mut self) -> Access {
+        self.reads.push(AccessKey::Transforms);
+        self
+    }
+
+    pub fn conflicts(&self, other: &Access) -> bool {
+        self.writes
+            .iter()
+            .any(|key| other.reads.contains(key) || other.writes.contains(key))
+            || other.writes.iter().any(|key| self.reads.contains(key))
+    }
+
+    fn can_read(&self, key: &AccessKey) -> bool {
+        self.reads.contains(key) || self.writes.contains(key)
+    }
+}
+
+// Shared view of the world handed to parallel behaviors, checked against their declared access.
+// Structural changes, tag edits and transform writes go through `commands`.
+pub struct WorldView<'a> {
+    pub(super) world: &'a World,
+    pub(super) access: &'a Access,
+    pub(super) behavior: &'static str,
+}
+
+impl WorldView<'_> {
+    pub fn read<T: Component>(&self) -> Option<MappedRwLockReadGuard<'_, SparseSet<T>>> {
+        self.check(
+            self.access
+                .can_read(&AccessKey::Component(TypeId::of::<T>())),
+            "read",
+            type_name::<T>(),
+        );
+        self.world.components.read::<T>()
+    }
+
+    pub fn write<T: Component>(&self) -> Option<MappedRwLockWriteGuard<'_, SparseSet<T>>> {
+        self.check(
+            self.access
+                .writes
+                .contains(&AccessKey::Component(TypeId::of::<T>())),
+            "write",
+            type_name::<T>(),
+        );
+        self.world.components.write::<T>()
+    }
+
+    pub fn resource<T: Component>(&self) -> Option<MappedRwLockReadGuard<'_, T>> {
+        self.check(
+            self.access
+                .can_read(&AccessKey::Resource(TypeId::of::<T>())),
+            "read",
+            type_name::<T>(),
+        );
+        self.world.resource::<T>()
+    }
+
+    pub fn resource_mut<T: Component>(&self) -> Option<MappedRwLockWriteGuard<'_, T>> {
+        self.check(
+            self.access
+                .writes
+                .contains(&AccessKey::Resource(TypeId::of::<T>())),
+            "write",
+            type_name::<T>(),
+        );
+        self.world.resource_write::<T>()
+    }
+
+    pub fn events<E: Component>(&self) -> MappedRwLockReadGuard<'_, Events<E>> {
+        self.check(
+            self.access.can_read(&AccessKey::Event(TypeId::of::<E>())),
+            "read",
+            type_name::<E>(),
+        );
+        self.world.events::<E>()
+    }
+
+    pub fn send<E: Component>(&self, event: E) {
+        self.check(
+            self.access
+                .writes
+                .contains(&AccessKey::Event(TypeId::of::<E>())),
+            "send",
+            type_name::<E>(),
+        );
+        self.world.send(event);
+    }
+
+    pub fn ids_by_tag(&self, tag: &str) -> &[EntityId] {
+        self.check(
+            self.access.can_read(&AccessKey::Tag(String::from(tag))),
+            "read tag",
+            tag,
+        );
+        self.world.ids_by_tag(tag)
+    }
+
+    pub fn query_tags(&self, query: TagQuery) -> impl Iterator<Item = EntityId> + '_ {
+        for tag in query
+            .all
+            .iter()
+            .chain(query.any.iter())
+            .chain(query.none.iter())
+        {
+            self.check(
+                self.access.can_read(&AccessKey::Tag(tag.clone())),
+                "read tag",
+                tag,
+            );
+        }
+        self.world.query_tags(query)
+    }
+
+    pub fn transform(&self, id: EntityId) -> Option<&EntityTransform> {
+        self.check(
+            self.access.can_read(&AccessKey::Transforms),
+            "read",
+            "transforms",
+        );
+        self.world.transform(id)
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.world.is_alive(id)
+    }
+
+    pub fn commands(&self) -> Commands<'_> {
+        self.world.commands()
+    }
+
+    fn check(&self, allowed: bool, what: &str, name: &str) {
+        if !allowed {
+            panic!(
+                "Behavior '{}' did not declare {what} access to {name}",
+                self.behavior
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use cgmath::{InnerSpace, Matrix4, Vector3, Vector4};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Self { min, max }
+    }
+
+    pub fn from_points(mut points: impl Iterator<Item = [f32; 3]>) -> Option<Aabb> {
+        let first = Vector3::from(points.next()?);
+        Some(points.fold(Self::new(first, first), |aabb, point| {
+            Self::new(
+                Vector3::new(
+                    aabb.min.x.min(point[0]),
+                    aabb.min.y.min(point[1]),
+                    aabb.min.z.min(point[2]),
+                ),
+                Vector3::new(
+                    aabb.max.x.max(point[0]),
+                    aabb.max.y.max(point[1]),
+                    aabb.max.z.max(point[2]),
+                ),
+            )
+        }))
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    // Loose world space sphere around the box, cheaper to test than the transformed corners
+    pub fn bounding_sphere(&self, model: &Matrix4<f32>) -> (Vector3<f32>, f32) {
+        let center = model * self.center().extend(1.0);
+        let max_scale = model
+            .x
+            .truncate()
+            .magnitude()
+            .max(model.y.truncate().magnitude())
+            .max(model.z.truncate().magnitude());
+
+        (
+            center.truncate(),
+            self.half_extents().magnitude() * max_scale,
+        )
+    }
+}
+
+// Planes face inwards and are taken from the rows of view_proj, using wgpu's 0..1 depth range
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Frustum {
+        let row = |i: usize| {
+            Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2]
+            .map(|plane| plane / plane.truncate().magnitude());
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_relative_eq, vec3};
+
+    use super::*;
+    use crate::engine::world::camera::Camera;
+
+    fn frustum() -> Frustum {
+        let camera = Camera::perspective(60.0, 0.1, 100.0);
+        Frustum::from_view_proj(&camera.make_projection_matrix(1.0))
+    }
+
+    #[test]
+    fn from_points_spans_every_point() {
+        let aabb = Aabb::from_points([[1.0, -2.0, 0.0], [-1.0, 3.0, 0.5]].into_iter()).unwrap();
+        assert_eq!(aabb.min, vec3(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, vec3(1.0, 3.0, 0.5));
+        assert!(Aabb::from_points(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn bounding_sphere_follows_translation_and_largest_scale() {
+        let aabb = Aabb::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+        let model = Matrix4::from_translation(vec3(5.0, 0.0, 0.0))
+            * Matrix4::from_nonuniform_scale(1.0, 3.0, 2.0);
+
+        let (center, radius) = aabb.bounding_sphere(&model);
+        assert_relative_eq!(center, vec3(5.0, 0.0, 0.0));
+        assert_relative_eq!(radius, 3.0_f32.sqrt() * 3.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn frustum_keeps_spheres_in_front_of_the_camera() {
+        let frustum = frustum();
+        assert!(frustum.intersects_sphere(vec3(0.0, 0.0, -10.0), 1.0));
+        assert!(!frustum.intersects_sphere(vec3(0.0, 0.0, 10.0), 1.0));
+        assert!(!frustum.intersects_sphere(vec3(0.0, 0.0, -200.0), 1.0));
+        assert!(!frustum.intersects_sphere(vec3(0.0, 0.0, -0.05), 0.01));
+    }
+
+    #[test]
+    fn frustum_keeps_spheres_overlapping_a_side() {
+        let frustum = frustum();
+        assert!(frustum.intersects_sphere(vec3(6.2, 0.0, -10.0), 1.0));
+        assert!(!frustum.intersects_sphere(vec3(8.0, 0.0, -10.0), 1.0));
+        assert!(!frustum.intersects_sphere(vec3(0.0, -50.0, -10.0), 1.0));
+    }
+}
@@ -0,0 +1,127 @@
+use cgmath::{ortho, perspective, Deg, Matrix4, Rad};
+use wgpu::Color;
+
+use super::RenderId;
+
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+pub enum Projection {
+    Perspective { fov_y: Rad<f32> },
+    Orthographic { height: f32 },
+}
+
+// Normalized to the size of the render target
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn full() -> Viewport {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+pub struct Camera {
+    pub projection: Projection,
+    pub near: f32,
+    pub far: f32,
+    pub viewport: Viewport,
+    pub target: Option<RenderId>,
+    pub clear_color: Color,
+    pub priority: i32,
+    pub active: bool,
+    // Entities are drawn when their layers share a bit with this mask
+    pub layers: u32,
+    pub shader: &'static str,
+}
+
+pub struct CameraView {
+    pub target: Option<RenderId>,
+    pub view_proj: Matrix4<f32>,
+    pub viewport: Viewport,
+    pub clear_color: Color,
+    // Only the first camera drawing to a target clears it
+    pub clear: bool,
+    pub shader: &'static str,
+}
+
+impl Camera {
+    pub fn perspective(fov_y: f32, near: f32, far: f32) -> Camera {
+        Self::new(
+            Projection::Perspective {
+                fov_y: Deg(fov_y).into(),
+            },
+            near,
+            far,
+        )
+    }
+
+    pub fn orthographic(height: f32, near: f32, far: f32) -> Camera {
+        Self::new(Projection::Orthographic { height }, near, far)
+    }
+
+    fn new(projection: Projection, near: f32, far: f32) -> Camera {
+        Self {
+            projection,
+            near,
+            far,
+            viewport: Viewport::full(),
+            target: None,
+            clear_color: Color::BLACK,
+            priority: 0,
+            active: true,
+            layers: u32::MAX,
+            shader: "shaders/entity.wgsl",
+        }
+    }
+
+    pub fn aspect(&self, target_width: u32, target_height: u32) -> f32 {
+        let width = self.viewport.width * target_width as f32;
+        let height = self.viewport.height * target_height as f32;
+
+        if height > 0.0 {
+            width / height
+        } else {
+            1.0
+        }
+    }
+
+    pub fn make_projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        let projection = match self.projection {
+            Projection::Perspective { fov_y } => perspective(fov_y, aspect, self.near, self.far),
+            Projection::Orthographic { height } => {
+                let half_h = height / 2.0;
+                let half_w = half_h * aspect;
+                ortho(-half_w, half_w, -half_h, half_h, self.near, self.far)
+            }
+        };
+
+        OPENGL_TO_WGPU_MATRIX * projection
+    }
+
+    pub fn make_view(&self, view: Matrix4<f32>, aspect: f32) -> CameraView {
+        CameraView {
+            target: self.target,
+            view_proj: self.make_projection_matrix(aspect) * view,
+            viewport: self.viewport,
+            clear_color: self.clear_color,
+            clear: true,
+            shader: self.shader,
+        }
+    }
+}
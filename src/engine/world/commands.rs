@@ -0,0 +1,96 @@
+use super::{
+    component::Component,
+    entity_renderer::{EntityModel, EntityRenderer, EntityTexture},
+    id::{EntityId, RenderId},
+    EntityTransform, World, WorldError,
+};
+
+type CommandFn = Box<dyn FnOnce(&mut World, &mut EntityRenderer) -> Result<(), WorldError> + Send>;
+
+pub enum Command {
+    Spawn {
+        id: EntityId,
+        tags: Vec<String>,
+        render: Option<RenderId>,
+    },
+    SpawnWithRender {
+        id: EntityId,
+        tags: Vec<String>,
+        texture: Option<EntityTexture>,
+        model: Option<EntityModel>,
+    },
+    Despawn(EntityId),
+    AddTag(EntityId, String),
+    RemoveTag(EntityId, String),
+    SetParent(EntityId, Option<EntityId>),
+    SetTransform(EntityId, EntityTransform),
+    Custom(CommandFn),
+}
+
+// Only needs `&World`, so commands can be queued while iterating the world's own id lists
+pub struct Commands<'a> {
+    pub(super) world: &'a World,
+}
+
+impl Commands<'_> {
+    pub fn spawn(&self, tags: &[&str], render: Option<RenderId>) -> EntityId {
+        let id = self.world.ids.lock().alloc();
+        self.push(Command::Spawn {
+            id,
+            tags: Vec::from_iter(tags.iter().map(|&tag| String::from(tag))),
+            render,
+        });
+        id
+    }
+
+    pub fn spawn_with_render(
+        &self,
+        tags: &[&str],
+        texture: Option<EntityTexture>,
+        model: Option<EntityModel>,
+    ) -> EntityId {
+        let id = self.world.ids.lock().alloc();
+        self.push(Command::SpawnWithRender {
+            id,
+            tags: Vec::from_iter(tags.iter().map(|&tag| String::from(tag))),
+            texture,
+            model,
+        });
+        id
+    }
+
+    pub fn despawn(&self, id: EntityId) {
+        self.push(Command::Despawn(id));
+    }
+
+    pub fn add_tag(&self, id: EntityId, tag: &str) {
+        self.push(Command::AddTag(id, String::from(tag)));
+    }
+
+    pub fn remove_tag(&self, id: EntityId, tag: &str) {
+        self.push(Command::RemoveTag(id, String::from(tag)));
+    }
+
+    pub fn set_parent(&self, id: EntityId, parent: Option<EntityId>) {
+        self.push(Command::SetParent(id, parent));
+    }
+
+    pub fn set_transform(&self, id: EntityId, transform: EntityTransform) {
+        self.push(Command::SetTransform(id, transform));
+    }
+
+    pub fn insert<T: Component>(&self, id: EntityId, component: T) {
+        self.add(move |world, _| world.insert(id, component).map(|_| ()));
+    }
+
+    pub fn add(
+        &self,
+        f: impl FnOnce(&mut World, &mut EntityRenderer) -> Result<(), WorldError> + Send + 'static,
+    ) {
+        self.push(Command::Custom(Box::new(f)));
+    }
+
+    fn push(&self, command: Command) {
+        self.world.commands.lock().push(command);
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+use super::id::{EntityId, GenerationalId};
+
+pub trait Component: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Component for T {}
+
+pub trait Query {
+    fn type_ids() -> Vec<TypeId>;
+}
+
+macro_rules! impl_query {
+    ($($t:ident),*) => {
+        impl<$($t: 'static),*> Query for ($($t,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$t>()),*]
+            }
+        }
+    };
+}
+
+impl_query!(A);
+impl_query!(A, B);
+impl_query!(A, B, C);
+impl_query!(A, B, C, D);
+impl_query!(A, B, C, D, E);
+impl_query!(A, B, C, D, E, F);
+
+trait ComponentStorage: Send + Sync {
+    fn contains(&self, id: EntityId) -> bool;
+    fn ids(&self) -> &[EntityId];
+    fn remove_entity(&mut self, id: EntityId);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Dense arrays keep iteration cache friendly, the sparse array maps an entity to its dense slot
+pub struct SparseSet<T> {
+    sparse: Vec<Option<usize>>,
+    ids: Vec<EntityId>,
+    dense: Vec<T>,
+}
+
+impl<T: Component> SparseSet<T> {
+    fn new() -> SparseSet<T> {
+        Self {
+            sparse: Vec::new(),
+            ids: Vec::new(),
+            dense: Vec::new(),
+        }
+    }
+
+    // The sparse array is indexed by slot, the dense id check rejects stale generations
+    fn slot(&self, id: EntityId) -> Option<usize> {
+        self.sparse
+            .get(id.index() as usize)
+            .copied()
+            .flatten()
+            .filter(|&slot| self.ids[slot] == id)
+    }
+
+    fn insert(&mut self, id: EntityId, value: T) -> Option<T> {
+        if let Some(slot) = self.slot(id) {
+            return Some(std::mem::replace(&mut self.dense[slot], value));
+        }
+
+        let index = id.index() as usize;
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+
+        if let Some(stale) = self.sparse[index] {
+            self.remove(self.ids[stale]);
+        }
+
+        self.sparse[index] = Some(self.dense.len());
+        self.ids.push(id);
+        self.dense.push(value);
+        None
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.slot(id).map(|slot| &self.dense[slot])
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.slot(id).map(|slot| &mut self.dense[slot])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.ids.iter().copied().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.ids.iter().copied().zip(self.dense.iter_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    fn remove(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slot(id)?;
+        self.sparse[id.index() as usize] = None;
+
+        let last = *self.ids.last().unwrap();
+        if last != id {
+            self.sparse[last.index() as usize] = Some(slot);
+        }
+
+        self.ids.swap_remove(slot);
+        Some(self.dense.swap_remove(slot))
+    }
+}
+
+impl<T: Component> ComponentStorage for SparseSet<T> {
+    fn contains(&self, id: EntityId) -> bool {
+        self.slot(id).is_some()
+    }
+
+    fn ids(&self) -> &[EntityId] {
+        self.ids.as_slice()
+    }
+
+    fn remove_entity(&mut self, id: EntityId) {
+        self.remove(id);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Storages sit behind their own locks so parallel behaviors can borrow disjoint component types
+pub struct Components {
+    storages: HashMap<TypeId, RwLock<Box<dyn ComponentStorage>>>,
+}
+
+impl Components {
+    pub fn new() -> Components {
+        Self {
+            storages: HashMap::new(),
+        }
+    }
+
+    pub fn insert<T: Component>(&mut self, id: EntityId, value: T) -> Option<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| RwLock::new(Box::new(SparseSet::<T>::new())))
+            .get_mut()
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()
+            .unwrap()
+            .insert(id, value)
+    }
+
+    pub fn get<T: Component>(&self, id: EntityId) -> Option<MappedRwLockReadGuard<'_, T>> {
+        MappedRwLockReadGuard::try_map(self.read::<T>()?, |storage| storage.get(id)).ok()
+    }
+
+    pub fn get_mut<T: Component>(&mut self, id: EntityId) -> Option<&mut T> {
+        self.storage_mut::<T>()?.get_mut(id)
+    }
+
+    pub fn remove<T: Component>(&mut self, id: EntityId) -> Option<T> {
+        self.storage_mut::<T>()?.remove(id)
+    }
+
+    pub fn remove_entity(&mut self, id: EntityId) {
+        for storage in self.storages.values_mut() {
+            storage.get_mut().remove_entity(id);
+        }
+    }
+
+    pub fn read<T: Component>(&self) -> Option<MappedRwLockReadGuard<'_, SparseSet<T>>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?.read();
+
+        Some(RwLockReadGuard::map(storage, |storage| {
+            storage.as_any().downcast_ref::<SparseSet<T>>().unwrap()
+        }))
+    }
+
+    pub fn write<T: Component>(&self) -> Option<MappedRwLockWriteGuard<'_, SparseSet<T>>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?.write();
+
+        Some(RwLockWriteGuard::map(storage, |storage| {
+            storage.as_any_mut().downcast_mut::<SparseSet<T>>().unwrap()
+        }))
+    }
+
+    pub fn iter_mut<T: Component>(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.storage_mut::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.iter_mut())
+    }
+
+    // Walks the smallest storage and keeps the entities present in every other one
+    pub fn query<Q: Query>(&self) -> Vec<EntityId> {
+        let storages = Q::type_ids()
+            .iter()
+            .map(|type_id| self.storages.get(type_id).map(|storage| storage.read()))
+            .collect::<Option<Vec<_>>>();
+
+        let storages = match storages {
+            Some(storages) => storages,
+            None => return Vec::new(),
+        };
+
+        let smallest = storages
+            .iter()
+            .min_by_key(|storage| storage.ids().len())
+            .unwrap();
+
+        Vec::from_iter(
+            smallest
+                .ids()
+                .iter()
+                .copied()
+                .filter(|&id| storages.iter().all(|storage| storage.contains(id))),
+        )
+    }
+
+    fn storage_mut<T: Component>(&mut self) -> Option<&mut SparseSet<T>> {
+        self.storages.get_mut(&TypeId::of::<T>()).map(|storage| {
+            storage
+                .get_mut()
+                .as_any_mut()
+                .downcast_mut::<SparseSet<T>>()
+                .unwrap()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(index: u32, generation: u32) -> EntityId {
+        EntityId::from_parts(index, generation)
+    }
+
+    #[test]
+    fn removal_keeps_the_moved_slot_reachable() {
+        let mut set = SparseSet::new();
+        set.insert(id(0, 0), "a");
+        set.insert(id(5, 0), "b");
+        set.insert(id(2, 0), "c");
+
+        assert_eq!(set.remove(id(0, 0)), Some("a"));
+        assert_eq!(set.get(id(2, 0)), Some(&"c"));
+        assert_eq!(set.get(id(5, 0)), Some(&"b"));
+        assert_eq!(set.get(id(0, 0)), None);
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn stale_generations_miss_and_get_replaced() {
+        let mut set = SparseSet::new();
+        set.insert(id(3, 0), 1);
+
+        assert_eq!(set.get(id(3, 1)), None);
+        assert_eq!(set.remove(id(3, 1)), None);
+
+        assert_eq!(set.insert(id(3, 1), 2), None);
+        assert_eq!(set.get(id(3, 0)), None);
+        assert_eq!(set.get(id(3, 1)), Some(&2));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn query_keeps_entities_with_every_component() {
+        let mut components = Components::new();
+        components.insert(id(0, 0), 1u32);
+        components.insert(id(1, 0), 2u32);
+        components.insert(id(1, 0), 0.5f32);
+        components.insert(id(2, 0), 1.5f32);
+
+        assert_eq!(components.query::<(u32, f32)>(), vec![id(1, 0)]);
+        assert!(components.query::<(u32, bool)>().is_empty());
+
+        components.remove_entity(id(1, 0));
+        assert!(components.query::<(u32, f32)>().is_empty());
+    }
+}
@@ -1,17 +1,29 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range, rc::Rc};
 
 use cgmath::Matrix4;
+use wgpu::*;
 
 use crate::engine::{
+    capture::FrameCapture,
+    rendergraph::{GraphResource, RenderGraph},
     resource::{
+        atlas::AtlasHandle,
         meshmanager::MeshHandle,
         model::ModelVertex,
-        texmanager::{TexDataFormat, TexHandle},
+        pipelinecache::PipelineKey,
+        readback::TexReadbackFuture,
+        texmanager::{TexDataFormat, TexHandle, TexKind, TexRegion},
+        texture::TextureData,
     },
     EngineResources,
 };
 
-use super::RenderId;
+use super::{
+    bounds::Aabb,
+    camera::CameraView,
+    id::{IdAllocator, RenderId},
+    WorldError,
+};
 
 pub enum SetVerticesData<'a> {
     Replace(&'a [ModelVertex]),
@@ -22,11 +34,24 @@ pub enum RenderTargetType {
     RGBA32Depth,
     RGBA32,
     Depth,
+    DepthCube,
+}
+
+pub enum CubemapSource {
+    Faces([&'static str; 6]),
+    Cross(&'static str),
 }
 
 pub enum EntityTexture {
     Resource(Vec<&'static str>),
+    AtlasedResource(Vec<&'static str>),
+    ResourceArray(Vec<&'static str>),
+    ResourceCube(CubemapSource),
+    ResourceVolume(&'static str, u32),
     DynamicRGBA(Vec<(u32, u32)>),
+    DynamicArray(u32, u32, u32),
+    DynamicCube(u32),
+    DynamicVolume(u32, u32, u32),
     RenderTarget {
         width: u32,
         height: u32,
@@ -41,29 +66,37 @@ pub enum EntityModel {
     Alias(RenderId),
 }
 
+// Images too large for the atlas get a texture of their own
+pub enum AtlasedTexture {
+    Atlas(AtlasHandle),
+    Tex(TexHandle),
+}
+
 pub struct RenderEntity {
     texture: Option<EntityTexture>,
     model: Option<EntityModel>,
     color_allocations: Vec<TexHandle>,
+    atlas_allocations: Vec<AtlasedTexture>,
     depth_allocation: Option<TexHandle>,
     mesh_allocation: Option<MeshHandle>,
+    bounds: Option<Aabb>,
 }
 
 pub struct EntityRendererStorage {
     renders: HashMap<RenderId, RenderEntity>,
-    id_counter: u64,
+    ids: IdAllocator,
 }
 
 impl EntityRendererStorage {
     pub fn new() -> EntityRendererStorage {
         Self {
             renders: HashMap::new(),
-            id_counter: 0,
+            ids: IdAllocator::new(),
         }
     }
 }
 
-pub struct RenderTask(RenderId, Vec<Matrix4<f32>>);
+pub struct RenderTask(pub RenderId, pub Vec<Matrix4<f32>>);
 
 pub struct EntityRenderer<'a> {
     pub storage: &'a mut EntityRendererStorage,
@@ -75,69 +108,21 @@ impl EntityRenderer<'_> {
         &mut self,
         texture: Option<EntityTexture>,
         model: Option<EntityModel>,
-    ) -> RenderId {
-        let mut colors = Vec::new();
-        let mut depth = None;
-        let mut mesh = None;
-
-        match texture {
-            Some(EntityTexture::Resource(ref paths)) => {
-                for path in paths.iter() {
-                    let tex = self
-                        .resources
-                        .resource_manager
-                        .textures
-                        .get_mut(*path)
-                        .unwrap();
-
-                    colors.push(self.resources.resource_manager.tex_manager.alloc_tex(
-                        &self.resources.renderer.device,
-                        &self.resources.renderer.queue,
-                        TexDataFormat::StaticRGBA8(tex.load().unwrap().as_ref()),
-                    ));
-                }
-            }
-            Some(EntityTexture::DynamicRGBA(ref sizes)) => {
-                for (w, h) in sizes.iter() {
-                    colors.push(self.resources.resource_manager.tex_manager.alloc_tex(
-                        &self.resources.renderer.device,
-                        &self.resources.renderer.queue,
-                        TexDataFormat::DynamicRGBA32(*w, *h),
-                    ));
-                }
-            }
-            Some(EntityTexture::RenderTarget {
-                width,
-                height,
-                ref ty,
-                post_enabled,
-            }) => {
-                if let RenderTargetType::RGBA32Depth | RenderTargetType::RGBA32 = ty {
-                    colors.push(self.resources.resource_manager.tex_manager.alloc_tex(
-                        &self.resources.renderer.device,
-                        &self.resources.renderer.queue,
-                        TexDataFormat::DynamicRGBA32(width, height),
-                    ));
-
-                    if post_enabled {
-                        colors.push(self.resources.resource_manager.tex_manager.alloc_tex(
-                            &self.resources.renderer.device,
-                            &self.resources.renderer.queue,
-                            TexDataFormat::DynamicRGBA32(width, height),
-                        ));
-                    }
-                }
-
-                if let RenderTargetType::RGBA32Depth | RenderTargetType::Depth = ty {
-                    depth = Some(self.resources.resource_manager.tex_manager.alloc_tex(
-                        &self.resources.renderer.device,
-                        &self.resources.renderer.queue,
-                        TexDataFormat::DynamicDepth(width, height),
-                    ));
-                }
-            }
-            None => {}
+    ) -> Result<RenderId, WorldError> {
+        let mut entity = RenderEntity {
+            texture: None,
+            model: None,
+            color_allocations: Vec::new(),
+            atlas_allocations: Vec::new(),
+            depth_allocation: None,
+            mesh_allocation: None,
+            bounds: None,
         };
+        if let Err(err) = self.alloc_textures(&texture, &mut entity) {
+            self.free_allocations(entity);
+            return Err(err);
+        }
+        entity.texture = texture;
 
         match &model {
             Some(EntityModel::Resource(path)) => {
@@ -150,48 +135,218 @@ impl EntityRenderer<'_> {
                     .load()
                     .unwrap();
 
-                mesh = Some(
+                entity.mesh_allocation = Some(
                     self.resources
                         .resource_manager
                         .mesh_manager
                         .alloc_mesh(model.vertices.len()),
                 );
+                entity.bounds = Aabb::from_points(model.vertices.iter().map(|vertex| vertex.pos));
             }
             Some(EntityModel::InitialSize(init_size)) => {
-                mesh = Some(
+                entity.mesh_allocation = Some(
                     self.resources
                         .resource_manager
                         .mesh_manager
                         .alloc_mesh(*init_size),
                 );
             }
-            Some(EntityModel::Alias(_)) => {}
+            Some(EntityModel::Alias(owner)) => {
+                entity.bounds = self.bounds(*owner);
+            }
             None => {}
         }
 
-        self.storage.renders.insert(
-            self.storage.id_counter,
-            RenderEntity {
-                texture,
-                model,
-                color_allocations: colors,
-                depth_allocation: depth,
-                mesh_allocation: mesh,
-            },
-        );
+        entity.model = model;
+
+        let handle = self.storage.ids.alloc();
+        self.storage.renders.insert(handle, entity);
 
-        let handle = self.storage.id_counter;
-        self.storage.id_counter += 1;
-        handle
+        Ok(handle)
     }
 
-    pub fn delete_render(&mut self, id: RenderId) {
-        let entity = self.storage.renders.remove(&id).unwrap();
+    // Fills in the texture allocations, on error the ones made so far are left in `entity` to free
+    fn alloc_textures(
+        &mut self,
+        texture: &Option<EntityTexture>,
+        entity: &mut RenderEntity,
+    ) -> Result<(), WorldError> {
+        match *texture {
+            Some(EntityTexture::Resource(ref paths)) => {
+                for path in paths.iter() {
+                    let tex = self.load_texture(path);
 
+                    entity
+                        .color_allocations
+                        .push(self.alloc_tex(TexDataFormat::StaticRGBA8(tex.as_ref()))?);
+                }
+            }
+            Some(EntityTexture::AtlasedResource(ref paths)) => {
+                for path in paths.iter() {
+                    let tex = self.load_texture(path);
+                    let resource_manager = &mut self.resources.resource_manager;
+
+                    if resource_manager.atlas.fits(tex.as_ref()) {
+                        entity.atlas_allocations.push(AtlasedTexture::Atlas(
+                            resource_manager.atlas.alloc(
+                                &self.resources.renderer.device,
+                                &self.resources.renderer.queue,
+                                &mut resource_manager.tex_manager,
+                                tex.as_ref(),
+                            ),
+                        ));
+                    } else {
+                        entity.atlas_allocations.push(AtlasedTexture::Tex(
+                            self.alloc_tex(TexDataFormat::StaticRGBA8(tex.as_ref()))?,
+                        ));
+                    }
+                }
+            }
+            Some(EntityTexture::ResourceArray(ref paths)) => {
+                let layers = Vec::from_iter(paths.iter().map(|path| self.load_texture(path)));
+                let layer_refs = Vec::from_iter(layers.iter().map(|layer| layer.as_ref()));
+
+                entity
+                    .color_allocations
+                    .push(self.alloc_tex(TexDataFormat::StaticRGBA8Array(layer_refs.as_slice()))?);
+            }
+            Some(EntityTexture::ResourceCube(ref source)) => {
+                let faces = match source {
+                    CubemapSource::Faces(paths) => paths.map(|path| self.load_texture(path)),
+                    CubemapSource::Cross(path) => self
+                        .load_texture(path)
+                        .cross_faces()
+                        .map_err(WorldError::Texture)?
+                        .map(Rc::new),
+                };
+
+                entity
+                    .color_allocations
+                    .push(self.alloc_tex(TexDataFormat::StaticRGBA8Cube(
+                        faces.each_ref().map(|face| face.as_ref()),
+                    ))?);
+            }
+            Some(EntityTexture::ResourceVolume(path, depth)) => {
+                let tex = self.load_texture(path);
+
+                entity
+                    .color_allocations
+                    .push(self.alloc_tex(TexDataFormat::StaticRGBA8Volume(tex.as_ref(), depth))?);
+            }
+            Some(EntityTexture::DynamicRGBA(ref sizes)) => {
+                for (w, h) in sizes.iter() {
+                    entity
+                        .color_allocations
+                        .push(self.alloc_tex(TexDataFormat::DynamicRGBA32(*w, *h))?);
+                }
+            }
+            Some(EntityTexture::DynamicArray(w, h, layers)) => {
+                entity
+                    .color_allocations
+                    .push(self.alloc_tex(TexDataFormat::DynamicRGBA32Array(w, h, layers))?);
+            }
+            Some(EntityTexture::DynamicCube(size)) => {
+                entity
+                    .color_allocations
+                    .push(self.alloc_tex(TexDataFormat::DynamicRGBA32Cube(size))?);
+            }
+            Some(EntityTexture::DynamicVolume(w, h, d)) => {
+                entity
+                    .color_allocations
+                    .push(self.alloc_tex(TexDataFormat::DynamicRGBA32Volume(w, h, d))?);
+            }
+            Some(EntityTexture::RenderTarget {
+                width,
+                height,
+                ref ty,
+                post_enabled,
+            }) => {
+                if let RenderTargetType::RGBA32Depth | RenderTargetType::RGBA32 = ty {
+                    entity
+                        .color_allocations
+                        .push(self.alloc_tex(TexDataFormat::DynamicRGBA32(width, height))?);
+
+                    if post_enabled {
+                        entity
+                            .color_allocations
+                            .push(self.alloc_tex(TexDataFormat::DynamicRGBA32(width, height))?);
+                    }
+                }
+
+                if let RenderTargetType::RGBA32Depth | RenderTargetType::Depth = ty {
+                    entity.depth_allocation =
+                        Some(self.alloc_tex(TexDataFormat::DynamicDepth(width, height))?);
+                }
+
+                if let RenderTargetType::DepthCube = ty {
+                    entity.depth_allocation =
+                        Some(self.alloc_tex(TexDataFormat::DynamicDepthCube(width))?);
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn alloc_tex(&mut self, tex_data: TexDataFormat) -> Result<TexHandle, WorldError> {
+        self.resources
+            .resource_manager
+            .tex_manager
+            .alloc_tex(
+                &self.resources.renderer.device,
+                &self.resources.renderer.queue,
+                tex_data,
+            )
+            .map_err(WorldError::Texture)
+    }
+
+    pub fn describe(&self, id: RenderId) -> Option<(Option<&EntityTexture>, Option<&EntityModel>)> {
+        let render = self.storage.renders.get(&id)?;
+        Some((render.texture.as_ref(), render.model.as_ref()))
+    }
+
+    // Local bounds of the model, `None` for dynamic meshes which are never culled
+    pub fn bounds(&self, id: RenderId) -> Option<Aabb> {
+        self.storage.renders.get(&id)?.bounds
+    }
+
+    pub fn is_alive(&self, id: RenderId) -> bool {
+        self.storage.renders.contains_key(&id)
+    }
+
+    fn get_render(&self, id: RenderId) -> Result<&RenderEntity, WorldError> {
+        self.storage
+            .renders
+            .get(&id)
+            .ok_or(WorldError::DeadRender(id))
+    }
+
+    // Goes through `World::delete_render` so entities stop pointing at the render
+    pub(super) fn delete_render(&mut self, id: RenderId) -> Result<(), WorldError> {
+        let entity = self
+            .storage
+            .renders
+            .remove(&id)
+            .ok_or(WorldError::DeadRender(id))?;
+        self.storage.ids.free(id);
+        self.free_allocations(entity);
+        Ok(())
+    }
+
+    fn free_allocations(&mut self, entity: RenderEntity) {
         for alloc in entity.color_allocations {
             self.resources.resource_manager.tex_manager.free_tex(alloc);
         }
 
+        for alloc in entity.atlas_allocations {
+            match alloc {
+                AtlasedTexture::Atlas(alloc) => self.resources.resource_manager.atlas.free(alloc),
+                AtlasedTexture::Tex(alloc) => {
+                    self.resources.resource_manager.tex_manager.free_tex(alloc)
+                }
+            }
+        }
+
         if let Some(alloc) = entity.depth_allocation {
             self.resources.resource_manager.tex_manager.free_tex(alloc);
         }
@@ -204,8 +359,12 @@ impl EntityRenderer<'_> {
         }
     }
 
-    pub fn set_vertices(&mut self, id: RenderId, data: SetVerticesData) {
-        let mut render = self.storage.renders.get_mut(&id).unwrap();
+    pub fn set_vertices(&mut self, id: RenderId, data: SetVerticesData) -> Result<(), WorldError> {
+        let render = self
+            .storage
+            .renders
+            .get_mut(&id)
+            .ok_or(WorldError::DeadRender(id))?;
 
         let buf_size = self
             .resources
@@ -254,9 +413,314 @@ impl EntityRenderer<'_> {
                 );
             }
         }
+        Ok(())
+    }
+
+    pub fn upload_uniform<T: bytemuck::Pod>(&mut self, data: &[T]) -> u32 {
+        self.resources
+            .resource_manager
+            .frame_alloc
+            .alloc_uniform(&self.resources.renderer.queue, data)
+    }
+
+    pub fn upload_storage<T: bytemuck::Pod>(&mut self, data: &[T]) -> u32 {
+        self.resources
+            .resource_manager
+            .frame_alloc
+            .alloc_storage(&self.resources.renderer.queue, data)
+    }
+
+    pub fn read_texture(
+        &self,
+        id: RenderId,
+        index: usize,
+    ) -> Result<TexReadbackFuture, WorldError> {
+        let tex = self
+            .get_render(id)?
+            .color_allocations
+            .get(index)
+            .ok_or(WorldError::NoTexture(id, index))?;
+
+        self.resources
+            .resource_manager
+            .tex_manager
+            .read_tex(
+                &self.resources.renderer.device,
+                &self.resources.renderer.queue,
+                tex,
+            )
+            .map_err(WorldError::Readback)
+    }
+
+    pub fn read_depth(&self, id: RenderId) -> Result<TexReadbackFuture, WorldError> {
+        let depth = self
+            .get_render(id)?
+            .depth_allocation
+            .as_ref()
+            .ok_or(WorldError::NoDepth(id))?;
+
+        self.resources
+            .resource_manager
+            .tex_manager
+            .read_tex(
+                &self.resources.renderer.device,
+                &self.resources.renderer.queue,
+                depth,
+            )
+            .map_err(WorldError::Readback)
+    }
+
+    // One entry per path of an `AtlasedResource`, in the order the paths were given
+    pub fn atlas_rects(&self, id: RenderId) -> Option<&[AtlasedTexture]> {
+        Some(self.get_render(id).ok()?.atlas_allocations.as_slice())
+    }
+
+    pub fn color_target(&self, id: RenderId, index: usize) -> Option<&TexHandle> {
+        self.get_render(id).ok()?.color_allocations.get(index)
+    }
+
+    pub fn depth_target(&self, id: RenderId) -> Option<&TexHandle> {
+        self.get_render(id).ok()?.depth_allocation.as_ref()
+    }
+
+    pub fn capture(&mut self) -> &mut FrameCapture {
+        &mut self.resources.renderer.capture
+    }
+
+    pub fn graph(&mut self) -> &mut RenderGraph {
+        &mut self.resources.render_graph
+    }
+
+    fn load_texture(&mut self, path: &str) -> Rc<TextureData> {
+        self.resources
+            .resource_manager
+            .textures
+            .get_mut(path)
+            .unwrap_or_else(|| panic!("Cannot find texture at '{path}'"))
+            .load()
+            .unwrap()
+    }
+
+    pub fn set_texture(
+        &mut self,
+        id: RenderId,
+        index: usize,
+        region: &TexRegion,
+        data: &[u8],
+    ) -> Result<(), WorldError> {
+        let render = self
+            .storage
+            .renders
+            .get(&id)
+            .ok_or(WorldError::DeadRender(id))?;
+
+        let tex = render
+            .color_allocations
+            .get(index)
+            .ok_or(WorldError::NoTexture(id, index))?;
+
+        self.resources
+            .resource_manager
+            .tex_manager
+            .write_region(&self.resources.renderer.queue, tex, region, data)
+            .map_err(WorldError::Texture)
+    }
+
+    pub fn target_size(&self, target: Option<RenderId>) -> Result<(u32, u32), WorldError> {
+        match target {
+            Some(id) => {
+                let render = self.get_render(id)?;
+                let handle = render
+                    .color_allocations
+                    .first()
+                    .or(render.depth_allocation.as_ref())
+                    .ok_or(WorldError::NotATarget(id))?;
+                let size = self
+                    .resources
+                    .resource_manager
+                    .tex_manager
+                    .get_size(handle.id());
+
+                Ok((size.width, size.height))
+            }
+            None => Ok((
+                self.resources.renderer.size.width,
+                self.resources.renderer.size.height,
+            )),
+        }
     }
 
-    //pub fn set_texture(&mut self, id: RenderId, index: usize, data: &[u8]) {}
+    // Mesh of a render, following aliases to the render that owns it
+    fn mesh_range(&self, render: &RenderEntity) -> Option<Range<u32>> {
+        let mesh = match render.model {
+            Some(EntityModel::Alias(owner)) => {
+                self.storage.renders.get(&owner)?.mesh_allocation.as_ref()
+            }
+            _ => render.mesh_allocation.as_ref(),
+        }?;
+        let range = self.resources.resource_manager.mesh_manager.get_range(mesh);
+
+        Some(range.start as u32..range.end as u32)
+    }
+
+    pub fn render(&mut self, camera: &CameraView, tasks: &[RenderTask]) -> Result<(), WorldError> {
+        let (width, height) = self.target_size(camera.target)?;
+        let tex_manager = &self.resources.resource_manager.tex_manager;
+        let (colors, depth) = match camera.target {
+            Some(id) => {
+                let render = self.get_render(id)?;
+                (
+                    render.color_allocations.first().map(|c| c.id()),
+                    render.depth_allocation.as_ref().map(|d| d.id()),
+                )
+            }
+            None => (None, None),
+        };
 
-    pub fn render(&mut self, target: RenderId, camera: &Matrix4<f32>, tasks: &[RenderTask]) {}
+        let mut key = PipelineKey::new(
+            camera.shader,
+            match (camera.target, colors) {
+                (None, _) => self.resources.renderer.surface_config.format,
+                (Some(_), Some(color)) => tex_manager.get_format(color),
+                (Some(_), None) => TextureFormat::Rgba8Unorm,
+            },
+        );
+        key.depth_format = depth.map(|depth| tex_manager.get_format(depth));
+        if camera.target.is_some() && colors.is_none() {
+            key.color_formats.clear();
+            key.fs_entry = None;
+        }
+        // Float targets aren't blendable, wgpu rejects the pipeline if blending is left on
+        if key
+            .color_formats
+            .iter()
+            .any(|format| !is_blendable(*format))
+        {
+            key.blend = None;
+        }
+
+        // Per draw data goes through the frame allocator, the pass only records commands
+        let mut draws = Vec::new();
+        for RenderTask(id, matrices) in tasks {
+            let render = self.get_render(*id)?;
+            let Some(vertices) = self.mesh_range(render) else {
+                continue;
+            };
+            // The entity shader only samples the 2D table, indices are only valid within their kind's table
+            let tex_manager = &self.resources.resource_manager.tex_manager;
+            let texture = render
+                .color_allocations
+                .iter()
+                .find(|tex| tex_manager.get_kind(tex) == TexKind::D2)
+                .map(|tex| (tex_manager.get_page(tex), tex_manager.get_index(tex) as u32));
+            let (page, tex_index) = texture.unwrap_or((0, 0));
+
+            let draw = DrawUniform {
+                view_proj: camera.view_proj.into(),
+                tex_index,
+                textured: texture.is_some() as u32,
+                _pad: [0; 2],
+            };
+            let uniform = self.upload_uniform(&[draw]);
+            let instances = Vec::from_iter(matrices.iter().map(|&m| -> [[f32; 4]; 4] { m.into() }));
+            let storage = self.upload_storage(instances.as_slice());
+
+            draws.push((page, uniform, storage, vertices, matrices.len() as u32));
+        }
+
+        if !draws.is_empty() {
+            self.resources
+                .resource_manager
+                .pipeline(&self.resources.renderer.device, &key);
+        }
+
+        let graph = &mut self.resources.render_graph;
+        let colors = match camera.target {
+            Some(_) => Vec::from_iter(colors.map(GraphResource::Texture)),
+            None => vec![GraphResource::Surface],
+        };
+        let depth = depth.map(GraphResource::Texture);
+
+        let viewport = camera.viewport;
+        let clear = camera.clear;
+        let clear_color = camera.clear_color;
+        graph.add_pass("Camera", &[], &colors, depth, move |ctx| {
+            let color_attachments = Vec::from_iter(ctx.colors.iter().map(|view| {
+                Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: if clear {
+                            LoadOp::Clear(clear_color)
+                        } else {
+                            LoadOp::Load
+                        },
+                        store: true,
+                    },
+                })
+            }));
+
+            let mut pass = ctx.encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Camera"),
+                color_attachments: color_attachments.as_slice(),
+                depth_stencil_attachment: ctx.depth.map(|view| RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(Operations {
+                        load: if clear {
+                            LoadOp::Clear(1.0)
+                        } else {
+                            LoadOp::Load
+                        },
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            let x = (viewport.x * width as f32).clamp(0.0, width as f32);
+            let y = (viewport.y * height as f32).clamp(0.0, height as f32);
+            let w = (viewport.width * width as f32).clamp(0.0, width as f32 - x);
+            let h = (viewport.height * height as f32).clamp(0.0, height as f32 - y);
+            if w < 1.0 || h < 1.0 {
+                return;
+            }
+            pass.set_viewport(x, y, w, h, 0.0, 1.0);
+            pass.set_scissor_rect(x as u32, y as u32, w as u32, h as u32);
+
+            if draws.is_empty() {
+                return;
+            }
+
+            let resources = ctx.resources;
+            pass.set_pipeline(resources.pipelines.cached(&key).unwrap());
+            pass.set_vertex_buffer(0, resources.mesh_manager.vertex_buffer.slice(..));
+
+            for (page, uniform, storage, vertices, instances) in draws {
+                pass.set_bind_group(0, resources.tex_manager.bind_group(page), &[]);
+                pass.set_bind_group(1, &resources.frame_alloc.bind_group, &[uniform, storage]);
+                pass.draw(vertices, 0..instances);
+            }
+        });
+        Ok(())
+    }
 }
+
+fn is_blendable(format: TextureFormat) -> bool {
+    format
+        .describe()
+        .guaranteed_format_features
+        .flags
+        .contains(TextureFormatFeatureFlags::BLENDABLE)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DrawUniform {
+    view_proj: [[f32; 4]; 4],
+    tex_index: u32,
+    textured: u32,
+    _pad: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for DrawUniform {}
+unsafe impl bytemuck::Zeroable for DrawUniform {}
@@ -0,0 +1,148 @@
+use std::{any::Any, marker::PhantomData, path::PathBuf};
+
+use super::{component::Component, id::EntityId};
+
+#[derive(Clone, Copy, Debug)]
+pub struct WindowResized {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WindowFocused(pub bool);
+
+#[derive(Clone, Debug)]
+pub struct FileDropped(pub PathBuf);
+
+#[derive(Clone, Copy, Debug)]
+pub struct EntitySpawned(pub EntityId);
+
+#[derive(Clone, Copy, Debug)]
+pub struct EntityDespawned(pub EntityId);
+
+pub(super) trait EventQueue: Send + Sync {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Events live for the frame they were sent in and the one after, so every reader gets a chance to see them
+pub struct Events<E> {
+    previous: Vec<E>,
+    current: Vec<E>,
+    start: usize,
+}
+
+impl<E: Component> Events<E> {
+    pub fn new() -> Events<E> {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+
+    pub fn read<'a>(&'a self, reader: &mut EventReader<E>) -> impl Iterator<Item = &'a E> {
+        let skip = reader.cursor.max(self.start) - self.start;
+        reader.cursor = self.start + self.previous.len() + self.current.len();
+        self.previous.iter().chain(self.current.iter()).skip(skip)
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<E: Component> EventQueue for Events<E> {
+    fn update(&mut self) {
+        self.start += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Held by each behavior that listens, remembers how far into the stream it has read
+pub struct EventReader<E> {
+    cursor: usize,
+    _marker: PhantomData<fn() -> E>,
+}
+
+impl<E> EventReader<E> {
+    pub fn new() -> EventReader<E> {
+        Self {
+            cursor: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(events: &Events<u32>, reader: &mut EventReader<u32>) -> Vec<u32> {
+        Vec::from_iter(events.read(reader).copied())
+    }
+
+    #[test]
+    fn readers_see_each_event_once_across_frames() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        assert_eq!(read(&events, &mut reader), vec![1]);
+
+        events.update();
+        events.send(2);
+        assert_eq!(read(&events, &mut reader), vec![2]);
+        assert!(read(&events, &mut reader).is_empty());
+    }
+
+    #[test]
+    fn events_survive_one_update_then_drop() {
+        let mut events = Events::new();
+        events.send(1);
+
+        events.update();
+        assert_eq!(read(&events, &mut EventReader::new()), vec![1]);
+
+        events.update();
+        assert!(events.is_empty());
+        assert!(read(&events, &mut EventReader::new()).is_empty());
+    }
+
+    #[test]
+    fn slow_readers_skip_dropped_events() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        events.update();
+        events.send(2);
+        events.update();
+        events.send(3);
+
+        assert_eq!(read(&events, &mut reader), vec![2, 3]);
+    }
+}
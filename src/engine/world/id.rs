@@ -0,0 +1,105 @@
+use std::fmt;
+
+pub trait GenerationalId: Copy {
+    fn from_parts(index: u32, generation: u32) -> Self;
+    fn index(&self) -> u32;
+    fn generation(&self) -> u32;
+}
+
+macro_rules! generational_id {
+    ($name:ident) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+        pub struct $name {
+            index: u32,
+            generation: u32,
+        }
+
+        impl GenerationalId for $name {
+            fn from_parts(index: u32, generation: u32) -> Self {
+                Self { index, generation }
+            }
+
+            fn index(&self) -> u32 {
+                self.index
+            }
+
+            fn generation(&self) -> u32 {
+                self.generation
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}v{}", self.index, self.generation)
+            }
+        }
+    };
+}
+
+generational_id!(EntityId);
+generational_id!(RenderId);
+
+// Freed slots are reused with a bumped generation so stale ids never match a live one
+pub struct IdAllocator {
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl IdAllocator {
+    pub fn new() -> IdAllocator {
+        Self {
+            generations: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn alloc<T: GenerationalId>(&mut self) -> T {
+        match self.free.pop() {
+            Some(index) => T::from_parts(index, self.generations[index as usize]),
+            None => {
+                self.generations.push(0);
+                T::from_parts(self.generations.len() as u32 - 1, 0)
+            }
+        }
+    }
+
+    pub fn free<T: GenerationalId>(&mut self, id: T) {
+        let generation = &mut self.generations[id.index() as usize];
+        if *generation == id.generation() {
+            *generation += 1;
+            self.free.push(id.index());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freed_slots_come_back_with_a_new_generation() {
+        let mut ids = IdAllocator::new();
+        let first: EntityId = ids.alloc();
+        let second: EntityId = ids.alloc();
+        ids.free(first);
+
+        let reused: EntityId = ids.alloc();
+        assert_eq!(reused.index(), first.index());
+        assert_eq!(reused.generation(), first.generation() + 1);
+        assert_ne!(reused, first);
+        assert_ne!(reused, second);
+    }
+
+    #[test]
+    fn freeing_a_stale_id_is_ignored() {
+        let mut ids = IdAllocator::new();
+        let id: RenderId = ids.alloc();
+        ids.free(id);
+        ids.free(id);
+
+        let reused: RenderId = ids.alloc();
+        let fresh: RenderId = ids.alloc();
+        assert_eq!(reused.index(), id.index());
+        assert_ne!(fresh.index(), id.index());
+    }
+}
@@ -1,59 +1,309 @@
+pub mod access;
+pub mod bounds;
+pub mod camera;
+pub mod commands;
+pub mod component;
 pub mod entity_renderer;
+pub mod events;
+pub mod id;
+pub mod prefab;
+pub mod scene;
+pub mod schedule;
+pub mod tags;
 
-use self::entity_renderer::{EntityRenderer, EntityRendererStorage};
-use super::EngineResources;
-use cgmath::{vec3, Matrix4, Rad, Vector3};
-use std::collections::{HashMap, HashSet};
+use self::{
+    access::{Access, WorldView},
+    bounds::{Aabb, Frustum},
+    camera::Camera,
+    commands::{Command, Commands},
+    component::{Component, Components, Query, SparseSet},
+    entity_renderer::{EntityRenderer, EntityRendererStorage, RenderTask},
+    events::{
+        EntityDespawned, EntitySpawned, EventQueue, Events, FileDropped, WindowFocused,
+        WindowResized,
+    },
+    id::{EntityId, IdAllocator, RenderId},
+    prefab::Prefab,
+    scene::SceneComponent,
+    schedule::{Batch, Schedule, Stage},
+    tags::TagQuery,
+};
+use super::{
+    resource::{readback::ReadbackError, texmanager::TexError},
+    EngineResources,
+};
+use cgmath::{
+    vec3, ElementWise, InnerSpace, Matrix3, Matrix4, One, Quaternion, Rad, Rotation, Rotation3,
+    SquareMatrix, Vector3, VectorSpace,
+};
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+use rayon::prelude::*;
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
-pub type EntityId = u64;
-pub type RenderId = u64;
+#[derive(Debug)]
+pub enum WorldError {
+    DeadEntity(EntityId),
+    DeadRender(RenderId),
+    ParentCycle(EntityId, EntityId),
+    NotATarget(RenderId),
+    NoTexture(RenderId, usize),
+    NoDepth(RenderId),
+    DuplicateBehavior(&'static str),
+    Readback(ReadbackError),
+    Texture(TexError),
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldError::DeadEntity(id) => write!(f, "Entity {id} is no longer alive"),
+            WorldError::DeadRender(id) => write!(f, "Render {id} is no longer alive"),
+            WorldError::ParentCycle(id, parent) => {
+                write!(
+                    f,
+                    "Cannot parent entity {id} to its own descendant {parent}"
+                )
+            }
+            WorldError::NotATarget(id) => write!(f, "Render {id} has no render target textures"),
+            WorldError::NoTexture(id, index) => write!(f, "Render {id} has no texture {index}"),
+            WorldError::NoDepth(id) => write!(f, "Render {id} has no depth texture"),
+            WorldError::DuplicateBehavior(name) => write!(
+                f,
+                "Behavior '{name}' is registered twice, give one of them its own name"
+            ),
+            WorldError::Readback(err) => write!(f, "{err}"),
+            WorldError::Texture(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
 
 struct EntityData {
     transform: EntityTransform,
+    previous: EntityTransform,
+    interpolate: bool,
+    // Set when this entity or an ancestor interpolates, so render matrices can't come from `world`
+    interpolated_tree: bool,
+    world: Matrix4<f32>,
+    dirty: bool,
+    parent: Option<EntityId>,
+    children: Vec<EntityId>,
     render: Option<RenderId>,
     visible: bool,
+    // Cleared when this entity or an ancestor is hidden, kept up to date by the world transform pass
+    visible_tree: bool,
+    layers: u32,
+    bounds: Option<Aabb>,
+}
+
+// State a parent hands down to its children in the world transform pass
+#[derive(Clone, Copy)]
+struct Inherited {
+    world: Matrix4<f32>,
+    interpolated: bool,
+    visible: bool,
+}
+
+impl Inherited {
+    fn root() -> Inherited {
+        Self {
+            world: Matrix4::identity(),
+            interpolated: false,
+            visible: true,
+        }
+    }
+}
+
+impl From<&EntityData> for Inherited {
+    fn from(entity: &EntityData) -> Inherited {
+        Self {
+            world: entity.world,
+            interpolated: entity.interpolated_tree,
+            visible: entity.visible_tree,
+        }
+    }
+}
+
+fn check_render(render: Option<RenderId>, renderer: &EntityRenderer) -> Result<(), WorldError> {
+    match render {
+        Some(render) if !renderer.is_alive(render) => Err(WorldError::DeadRender(render)),
+        _ => Ok(()),
+    }
 }
 
-pub trait WorldBehavior {
+// New entities only live on layer 0
+pub const DEFAULT_LAYERS: u32 = 1;
+
+// Send + Sync because behaviors with declared access run on the rayon thread pool
+pub trait WorldBehavior: Send + Sync {
     fn tags(&mut self) -> &[&'static str];
     fn init(&mut self, world: &mut World, renderer: &mut EntityRenderer);
     fn on_created(&mut self, world: &mut World, renderer: &mut EntityRenderer, ids: &[EntityId]);
     fn on_deleted(&mut self, world: &mut World, renderer: &mut EntityRenderer, ids: &[EntityId]);
     fn run(&mut self, world: &mut World, renderer: &mut EntityRenderer, dt: f32);
+
+    fn fixed_update(&mut self, _world: &mut World, _renderer: &mut EntityRenderer, _dt: f32) {}
+
+    // Names must be unique within a world, override this to register a behavior type more than once
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Update
+    }
+
+    fn before(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn after(&self) -> &[&'static str] {
+        &[]
+    }
+
+    // Returning `Some` moves the behavior onto the thread pool, where `run_parallel` replaces `run`
+    fn access(&self) -> Option<Access> {
+        None
+    }
+
+    // Only called for behaviors whose `access` returns `Some`, they must override it to do anything
+    fn run_parallel(&mut self, _world: &WorldView, _dt: f32) {}
 }
 
+#[derive(Clone, Copy)]
 pub struct EntityTransform {
     pub pos: Vector3<f32>,
-    pub rot: Vector3<f32>,
+    pub rot: Quaternion<f32>,
     pub scale: Vector3<f32>,
 }
 
 impl EntityTransform {
+    pub fn new() -> EntityTransform {
+        Self {
+            pos: vec3(0.0, 0.0, 0.0),
+            rot: Quaternion::one(),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
     pub fn make_model_matrix(&self) -> Matrix4<f32> {
-        Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
-            * Matrix4::from_angle_z(Rad(self.rot.z))
-            * Matrix4::from_angle_x(Rad(self.rot.x))
-            * Matrix4::from_angle_y(Rad(self.rot.y))
-            * Matrix4::from_translation(self.pos)
+        Matrix4::from_translation(self.pos)
+            * Matrix4::from(self.rot)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
     }
 
+    // Inverse of translation * rotation, scale is ignored for views
     pub fn make_view_matrix(&self) -> Matrix4<f32> {
-        Matrix4::from_translation(-self.pos)
-            * Matrix4::from_angle_z(Rad(-self.rot.z))
-            * Matrix4::from_angle_x(Rad(-self.rot.x))
-            * Matrix4::from_angle_y(Rad(-self.rot.y))
+        Matrix4::from(self.rot.conjugate()) * Matrix4::from_translation(-self.pos)
+    }
+
+    // Places `local` inside this transform the way a child would be, exact only for uniform scale
+    pub fn compose(&self, local: &EntityTransform) -> EntityTransform {
+        EntityTransform {
+            pos: self.pos
+                + self
+                    .rot
+                    .rotate_vector(self.scale.mul_element_wise(local.pos)),
+            rot: self.rot * local.rot,
+            scale: self.scale.mul_element_wise(local.scale),
+        }
+    }
+
+    pub fn lerp(&self, other: &EntityTransform, t: f32) -> EntityTransform {
+        EntityTransform {
+            pos: self.pos.lerp(other.pos, t),
+            rot: if self.rot.dot(other.rot) < 0.0 {
+                self.rot.nlerp(-other.rot, t)
+            } else {
+                self.rot.nlerp(other.rot, t)
+            },
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        self.rot.rotate_vector(-Vector3::unit_z())
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.rot.rotate_vector(Vector3::unit_x())
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.rot.rotate_vector(Vector3::unit_y())
+    }
+
+    pub fn look_at(&mut self, target: Vector3<f32>, up: Vector3<f32>) {
+        let back = (self.pos - target).normalize();
+        let right = up.cross(back).normalize();
+        let up = back.cross(right);
+
+        self.rot = Quaternion::from(Matrix3::from_cols(right, up, back)).normalize();
+    }
+
+    pub fn rotate(&mut self, axis: Vector3<f32>, angle: Rad<f32>) {
+        self.rot = (Quaternion::from_axis_angle(axis.normalize(), angle) * self.rot).normalize();
+    }
+
+    pub fn rotate_around(&mut self, point: Vector3<f32>, axis: Vector3<f32>, angle: Rad<f32>) {
+        let rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+
+        self.pos = point + rotation.rotate_vector(self.pos - point);
+        self.rot = (rotation * self.rot).normalize();
+    }
+
+    // Euler angles are (pitch, yaw, roll) in radians, applied as yaw * pitch * roll
+    pub fn from_euler(euler: Vector3<f32>) -> Quaternion<f32> {
+        Quaternion::from_angle_y(Rad(euler.y))
+            * Quaternion::from_angle_x(Rad(euler.x))
+            * Quaternion::from_angle_z(Rad(euler.z))
+    }
+
+    pub fn set_euler(&mut self, euler: Vector3<f32>) {
+        self.rot = Self::from_euler(euler);
+    }
+
+    pub fn euler(&self) -> Vector3<f32> {
+        let m = Matrix3::from(self.rot);
+        let pitch = (-m.z.y).clamp(-1.0, 1.0).asin();
+
+        if m.z.y.abs() < 0.9999 {
+            vec3(pitch, m.z.x.atan2(m.z.z), m.x.y.atan2(m.y.y))
+        } else {
+            vec3(pitch, (-m.x.z).atan2(m.x.x), 0.0)
+        }
     }
 }
 
 pub struct World {
-    id_counter: u64,
+    ids: Mutex<IdAllocator>,
+    commands: Mutex<Vec<Command>>,
 
     behaviors: Option<Box<Vec<Box<dyn WorldBehavior>>>>,
+    schedule: Schedule,
+
+    fixed_dt: f32,
+    max_substeps: u32,
+    accumulator: f32,
+    alpha: f32,
     renderer_storage: Option<Box<EntityRendererStorage>>,
 
     render_to_entities: HashMap<RenderId, Vec<EntityId>>,
 
     entities: HashMap<EntityId, EntityData>,
+    cameras: HashMap<EntityId, Camera>,
+    components: Components,
+    resources: HashMap<TypeId, RwLock<Box<dyn Any + Send + Sync>>>,
+    events: HashMap<TypeId, RwLock<Box<dyn EventQueue>>>,
+    scene_components: Vec<SceneComponent>,
+    prefabs: HashMap<String, Prefab>,
     tag_to_entities: HashMap<String, Vec<EntityId>>,
     entity_to_tags: HashMap<EntityId, Vec<String>>,
 
@@ -63,96 +313,705 @@ pub struct World {
 }
 
 impl World {
-    pub fn new(behaviors: Vec<Box<dyn WorldBehavior>>) -> World {
-        World {
-            id_counter: 0,
+    pub fn new(mut behaviors: Vec<Box<dyn WorldBehavior>>) -> Result<World, WorldError> {
+        let mut tag_to_behavior_indices: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, behavior) in behaviors.iter_mut().enumerate() {
+            for &tag in behavior.tags() {
+                tag_to_behavior_indices
+                    .entry(String::from(tag))
+                    .or_default()
+                    .push(idx);
+            }
+        }
+
+        let mut world = World {
+            ids: Mutex::new(IdAllocator::new()),
+            commands: Mutex::new(Vec::new()),
+            resources: HashMap::new(),
+            events: HashMap::new(),
+            scene_components: Vec::new(),
+            prefabs: HashMap::new(),
             renderer_storage: Some(Box::new(EntityRendererStorage::new())),
             render_to_entities: HashMap::new(),
             entities: HashMap::new(),
+            cameras: HashMap::new(),
+            components: Components::new(),
             tag_to_entities: HashMap::new(),
             entity_to_tags: HashMap::new(),
-            tag_to_behavior_indices: HashMap::new(),
+            tag_to_behavior_indices,
             behavior_to_created: Vec::from_iter((0..behaviors.len()).map(|_| HashSet::new())),
             behavior_to_deleted: Vec::from_iter((0..behaviors.len()).map(|_| HashSet::new())),
+            schedule: Schedule::new(behaviors.as_slice())?,
+            fixed_dt: 1.0 / 60.0,
+            max_substeps: 5,
+            accumulator: 0.0,
+            alpha: 0.0,
             behaviors: Some(Box::new(behaviors)),
-        }
+        };
+
+        world.add_event::<WindowResized>();
+        world.add_event::<WindowFocused>();
+        world.add_event::<FileDropped>();
+        world.add_event::<EntitySpawned>();
+        world.add_event::<EntityDespawned>();
+        Ok(world)
+    }
+
+    pub fn create_entity(
+        &mut self,
+        tags: &[&str],
+        render: Option<RenderId>,
+        renderer: &EntityRenderer,
+    ) -> Result<EntityId, WorldError> {
+        check_render(render, renderer)?;
+        let id = self.ids.get_mut().alloc();
+        self.spawn_reserved(id, tags, render);
+        Ok(id)
     }
 
-    pub fn create_entity(&mut self, tags: &[&str], render: Option<RenderId>) -> EntityId {
-        let id = self.id_counter;
-        self.id_counter += 1;
+    // Entities drawing the render are kept alive and simply stop drawing
+    pub fn delete_render(
+        &mut self,
+        id: RenderId,
+        renderer: &mut EntityRenderer,
+    ) -> Result<(), WorldError> {
+        renderer.delete_render(id)?;
+        for entity in self.render_to_entities.remove(&id).into_iter().flatten() {
+            self.entities.get_mut(&entity).unwrap().render = None;
+        }
+        Ok(())
+    }
 
+    fn spawn_reserved(&mut self, id: EntityId, tags: &[&str], render: Option<RenderId>) {
         self.entities.insert(
             id,
             EntityData {
-                transform: EntityTransform {
-                    pos: vec3(0.0, 0.0, 0.0),
-                    rot: vec3(0.0, 0.0, 0.0),
-                    scale: vec3(1.0, 1.0, 1.0),
-                },
+                transform: EntityTransform::new(),
+                previous: EntityTransform::new(),
+                interpolate: false,
+                interpolated_tree: false,
+                world: Matrix4::identity(),
+                dirty: true,
+                parent: None,
+                children: Vec::new(),
                 render,
                 visible: true,
+                visible_tree: true,
+                layers: DEFAULT_LAYERS,
+                bounds: None,
             },
         );
 
-        let mut tag_vec = Vec::new();
+        self.entity_to_tags.insert(id, Vec::new());
         for &tag in tags {
-            tag_vec.push(String::from(tag));
-            if !self.tag_to_entities.contains_key(tag) {
-                self.tag_to_entities.insert(tag.to_string(), Vec::new());
-            }
-            self.tag_to_entities.get_mut(tag).unwrap().push(id);
-
-            for &idx in self.tag_to_behavior_indices.get(tag).unwrap().iter() {
-                self.behavior_to_created.get_mut(idx).unwrap().insert(id);
-            }
+            self.add_tag(id, tag).unwrap();
         }
 
-        self.entity_to_tags.insert(id, tag_vec);
-
         if let Some(render_id) = render {
             self.render_to_entities
-                .get_mut(&render_id)
-                .unwrap()
+                .entry(render_id)
+                .or_default()
                 .push(id);
         }
-        id
+
+        self.send(EntitySpawned(id));
+    }
+
+    pub fn delete_entity(&mut self, id: EntityId) -> Result<(), WorldError> {
+        self.set_parent(id, None)?;
+        self.delete_subtree(id);
+        Ok(())
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.entities.contains_key(&id)
     }
 
-    pub fn delete_entity(&mut self, id: EntityId) {
+    fn delete_subtree(&mut self, id: EntityId) {
         let entity = self.entities.remove(&id).unwrap();
+        self.ids.get_mut().free(id);
+        for &child in entity.children.iter() {
+            self.delete_subtree(child);
+        }
+
+        self.cameras.remove(&id);
+        self.components.remove_entity(id);
         if let Some(render_id) = entity.render {
             let renders = self.render_to_entities.get_mut(&render_id).unwrap();
             let rm_idx = renders.iter().position(|&ent| ent == id).unwrap();
             renders.swap_remove(rm_idx);
         }
 
-        let tags = self.entity_to_tags.remove(&id).unwrap();
+        let tags = self.entity_to_tags.get(&id).unwrap().clone();
         for tag in tags.iter() {
-            let tag_vec = self.tag_to_entities.get_mut(tag).unwrap();
-            let rm_idx = tag_vec.iter().position(|&ent| ent == id).unwrap();
-            tag_vec.swap_remove(rm_idx);
+            self.remove_tag(id, tag).unwrap();
+        }
+        self.entity_to_tags.remove(&id);
+        self.send(EntityDespawned(id));
+    }
 
-            for &idx in self.tag_to_behavior_indices.get(tag).unwrap().iter() {
-                self.behavior_to_deleted.get_mut(idx).unwrap().insert(id);
-            }
+    // Behaviors are notified only when the entity starts or stops matching any of their tags
+    pub fn add_tag(&mut self, id: EntityId, tag: &str) -> Result<(), WorldError> {
+        let tags = self
+            .entity_to_tags
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?;
+        if tags.iter().any(|t| t == tag) {
+            return Ok(());
         }
+
+        let before = Self::behaviors_for(&self.tag_to_behavior_indices, tags);
+        tags.push(String::from(tag));
+        let after = Self::behaviors_for(&self.tag_to_behavior_indices, tags);
+
+        self.tag_to_entities
+            .entry(String::from(tag))
+            .or_default()
+            .push(id);
+
+        for idx in after.difference(&before) {
+            self.behavior_to_created.get_mut(*idx).unwrap().insert(id);
+        }
+        Ok(())
+    }
+
+    pub fn remove_tag(&mut self, id: EntityId, tag: &str) -> Result<(), WorldError> {
+        let tags = self
+            .entity_to_tags
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?;
+        let Some(tag_idx) = tags.iter().position(|t| t == tag) else {
+            return Ok(());
+        };
+
+        let before = Self::behaviors_for(&self.tag_to_behavior_indices, tags);
+        tags.swap_remove(tag_idx);
+        let after = Self::behaviors_for(&self.tag_to_behavior_indices, tags);
+
+        let tag_vec = self.tag_to_entities.get_mut(tag).unwrap();
+        let rm_idx = tag_vec.iter().position(|&ent| ent == id).unwrap();
+        tag_vec.swap_remove(rm_idx);
+
+        for idx in before.difference(&after) {
+            self.behavior_to_deleted.get_mut(*idx).unwrap().insert(id);
+        }
+        Ok(())
+    }
+
+    fn behaviors_for(
+        tag_to_behavior_indices: &HashMap<String, Vec<usize>>,
+        tags: &[String],
+    ) -> HashSet<usize> {
+        HashSet::from_iter(
+            tags.iter()
+                .filter_map(|tag| tag_to_behavior_indices.get(tag))
+                .flatten()
+                .copied(),
+        )
+    }
+
+    pub fn has_tag(&self, id: EntityId, tag: &str) -> bool {
+        self.entity_to_tags
+            .get(&id)
+            .is_some_and(|tags| tags.iter().any(|t| t == tag))
+    }
+
+    // Candidates come from the smallest required tag list, or every entity if nothing is required
+    pub fn query_tags(&self, query: TagQuery) -> impl Iterator<Item = EntityId> + '_ {
+        let candidates = match query
+            .all
+            .iter()
+            .map(|tag| self.ids_by_tag(tag))
+            .min_by_key(|ids| ids.len())
+        {
+            Some(ids) => ids.to_vec(),
+            None => Vec::from_iter(self.entities.keys().copied()),
+        };
+
+        candidates
+            .into_iter()
+            .filter(move |id| query.matches(self.entity_to_tags.get(id).unwrap()))
     }
 
     pub fn get_render(&self, id: EntityId) -> Option<RenderId> {
-        self.entities.get(&id).unwrap().render
+        self.entities.get(&id)?.render
+    }
+
+    pub fn ids_by_tag<'a>(&self, tag: &str) -> &[EntityId] {
+        self.tag_to_entities
+            .get(tag)
+            .map(|ids| ids.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn tags_by_id<'a>(&self, id: EntityId) -> Option<&[String]> {
+        self.entity_to_tags.get(&id).map(|tags| tags.as_slice())
+    }
+
+    pub fn transform(&self, id: EntityId) -> Option<&EntityTransform> {
+        Some(&self.entities.get(&id)?.transform)
+    }
+
+    pub fn transform_by_id<'a>(&mut self, id: EntityId) -> Option<&mut EntityTransform> {
+        let entity = self.entities.get_mut(&id)?;
+        entity.dirty = true;
+        Some(&mut entity.transform)
+    }
+
+    pub fn set_parent(&mut self, id: EntityId, parent: Option<EntityId>) -> Result<(), WorldError> {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err(WorldError::ParentCycle(id, parent.unwrap()));
+            }
+            ancestor = self
+                .entities
+                .get(&ancestor_id)
+                .ok_or(WorldError::DeadEntity(ancestor_id))?
+                .parent;
+        }
+
+        let entity = self
+            .entities
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?;
+        let old_parent = std::mem::replace(&mut entity.parent, parent);
+        entity.dirty = true;
+
+        if let Some(old_parent) = old_parent {
+            let siblings = &mut self.entities.get_mut(&old_parent).unwrap().children;
+            let rm_idx = siblings.iter().position(|&ent| ent == id).unwrap();
+            siblings.remove(rm_idx);
+        }
+
+        if let Some(parent) = parent {
+            self.entities.get_mut(&parent).unwrap().children.push(id);
+        }
+        Ok(())
+    }
+
+    pub fn parent(&self, id: EntityId) -> Option<EntityId> {
+        self.entities.get(&id)?.parent
+    }
+
+    pub fn children(&self, id: EntityId) -> &[EntityId] {
+        self.entities
+            .get(&id)
+            .map(|entity| entity.children.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn world_transform(&mut self, id: EntityId) -> Option<Matrix4<f32>> {
+        if !self.is_alive(id) {
+            return None;
+        }
+
+        let mut dirty_root = None;
+        let mut current = Some(id);
+
+        while let Some(current_id) = current {
+            let entity = self.entities.get(&current_id).unwrap();
+            if entity.dirty {
+                dirty_root = Some(current_id);
+            }
+            current = entity.parent;
+        }
+
+        if let Some(root) = dirty_root {
+            let parent = self.inherited(root);
+            self.update_world(root, parent, true);
+        }
+
+        Some(self.entities.get(&id).unwrap().world)
+    }
+
+    // Recomputes only dirty subtrees, clean parents pass their cached matrix down
+    pub fn update_world_transforms(&mut self) {
+        let roots = Vec::from_iter(
+            self.entities
+                .iter()
+                .filter(|(_, entity)| entity.parent.is_none())
+                .map(|(&id, _)| id),
+        );
+
+        for root in roots {
+            self.update_world(root, Inherited::root(), false);
+        }
+    }
+
+    fn inherited(&self, id: EntityId) -> Inherited {
+        self.entities
+            .get(&id)
+            .unwrap()
+            .parent
+            .map(|parent| Inherited::from(self.entities.get(&parent).unwrap()))
+            .unwrap_or(Inherited::root())
+    }
+
+    fn update_world(&mut self, id: EntityId, parent: Inherited, parent_changed: bool) {
+        let entity = self.entities.get_mut(&id).unwrap();
+        let changed = parent_changed || entity.dirty;
+
+        if changed {
+            entity.world = parent.world * entity.transform.make_model_matrix();
+            entity.interpolated_tree = parent.interpolated || entity.interpolate;
+            entity.visible_tree = parent.visible && entity.visible;
+            entity.dirty = false;
+        }
+
+        let inherited = Inherited::from(&*entity);
+        for child in entity.children.clone() {
+            self.update_world(child, inherited, changed);
+        }
+    }
+
+    pub fn insert<T: Component>(
+        &mut self,
+        id: EntityId,
+        component: T,
+    ) -> Result<Option<T>, WorldError> {
+        if !self.is_alive(id) {
+            return Err(WorldError::DeadEntity(id));
+        }
+        Ok(self.components.insert(id, component))
+    }
+
+    // Storages sit behind locks for parallel behaviors, so this returns a guard rather than `&T`
+    pub fn get<T: Component>(&self, id: EntityId) -> Option<MappedRwLockReadGuard<'_, T>> {
+        self.components.get(id)
+    }
+
+    pub fn get_mut<T: Component>(&mut self, id: EntityId) -> Option<&mut T> {
+        self.components.get_mut(id)
     }
 
-    pub fn ids_by_tag<'a>(&self, tag: &str) -> &Vec<EntityId> {
-        self.tag_to_entities.get(tag).unwrap()
+    pub fn remove<T: Component>(&mut self, id: EntityId) -> Option<T> {
+        self.components.remove(id)
     }
 
-    pub fn tags_by_id<'a>(&self, id: EntityId) -> &Vec<String> {
-        self.entity_to_tags.get(&id).unwrap()
+    pub fn query<Q: Query>(&self) -> Vec<EntityId> {
+        self.components.query::<Q>()
     }
 
-    pub fn transform_by_id<'a>(&mut self, id: EntityId) -> &mut EntityTransform {
-        &mut self.entities.get_mut(&id).unwrap().transform
+    pub fn read<T: Component>(&self) -> Option<MappedRwLockReadGuard<'_, SparseSet<T>>> {
+        self.components.read()
+    }
+
+    // Borrows the world mutably to skip the lock, use `read` to iterate from `&World`
+    pub fn iter<T: Component>(&mut self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.components
+            .iter_mut()
+            .map(|(id, component)| (id, &*component))
+    }
+
+    pub fn iter_mut<T: Component>(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.components.iter_mut()
+    }
+
+    pub fn insert_resource<T: Component>(&mut self, resource: T) {
+        self.resources
+            .insert(TypeId::of::<T>(), RwLock::new(Box::new(resource)));
+    }
+
+    pub fn resource<T: Component>(&self) -> Option<MappedRwLockReadGuard<'_, T>> {
+        let resource = self.resources.get(&TypeId::of::<T>())?.read();
+        Some(RwLockReadGuard::map(resource, |resource| {
+            resource.downcast_ref::<T>().unwrap()
+        }))
+    }
+
+    pub fn resource_write<T: Component>(&self) -> Option<MappedRwLockWriteGuard<'_, T>> {
+        let resource = self.resources.get(&TypeId::of::<T>())?.write();
+        Some(RwLockWriteGuard::map(resource, |resource| {
+            resource.downcast_mut::<T>().unwrap()
+        }))
+    }
+
+    pub fn resource_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())?
+            .get_mut()
+            .downcast_mut::<T>()
+    }
+
+    pub fn remove_resource<T: Component>(&mut self) -> Option<T> {
+        let resource = self.resources.remove(&TypeId::of::<T>())?;
+        Some(*resource.into_inner().downcast::<T>().unwrap())
+    }
+
+    pub fn add_event<E: Component>(&mut self) {
+        self.events
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| RwLock::new(Box::new(Events::<E>::new())));
+    }
+
+    pub fn send<E: Component>(&self, event: E) {
+        let mut events = self.event_queue::<E>().write();
+        events
+            .as_any_mut()
+            .downcast_mut::<Events<E>>()
+            .unwrap()
+            .send(event);
+    }
+
+    pub fn events<E: Component>(&self) -> MappedRwLockReadGuard<'_, Events<E>> {
+        RwLockReadGuard::map(self.event_queue::<E>().read(), |events| {
+            events.as_any().downcast_ref::<Events<E>>().unwrap()
+        })
+    }
+
+    fn event_queue<E: Component>(&self) -> &RwLock<Box<dyn EventQueue>> {
+        self.events.get(&TypeId::of::<E>()).unwrap_or_else(|| {
+            panic!(
+                "Event {} was never registered with add_event",
+                std::any::type_name::<E>()
+            )
+        })
+    }
+
+    pub fn set_camera(&mut self, id: EntityId, camera: Camera) -> Result<(), WorldError> {
+        if !self.is_alive(id) {
+            return Err(WorldError::DeadEntity(id));
+        }
+        self.cameras.insert(id, camera);
+        Ok(())
+    }
+
+    pub fn camera_by_id(&mut self, id: EntityId) -> Option<&mut Camera> {
+        self.cameras.get_mut(&id)
+    }
+
+    pub fn remove_camera(&mut self, id: EntityId) -> Option<Camera> {
+        self.cameras.remove(&id)
+    }
+
+    pub fn set_fixed_rate(&mut self, hz: f32) {
+        self.fixed_dt = 1.0 / hz;
+    }
+
+    pub fn set_max_substeps(&mut self, max_substeps: u32) {
+        self.max_substeps = max_substeps;
+    }
+
+    // Interpolated entities render between their last two fixed step states
+    pub fn set_interpolated(&mut self, id: EntityId, interpolate: bool) -> Result<(), WorldError> {
+        let entity = self
+            .entities
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?;
+        // Syncing only when turning it on keeps an already interpolating entity from snapping
+        if interpolate && !entity.interpolate {
+            entity.previous = entity.transform;
+        }
+        entity.interpolate = interpolate;
+        entity.dirty = true;
+        Ok(())
+    }
+
+    pub fn set_visible(&mut self, id: EntityId, visible: bool) -> Result<(), WorldError> {
+        let entity = self
+            .entities
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?;
+        entity.visible = visible;
+        entity.dirty = true;
+        Ok(())
+    }
+
+    // Hiding an entity hides its whole subtree. Exact even before the world transform pass,
+    // rendering reads the flag that pass propagates instead
+    pub fn is_visible(&self, id: EntityId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            match self.entities.get(&id) {
+                Some(entity) if entity.visible => current = entity.parent,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    pub fn set_layers(&mut self, id: EntityId, layers: u32) -> Result<(), WorldError> {
+        self.entities
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?
+            .layers = layers;
+        Ok(())
+    }
+
+    pub fn layers(&self, id: EntityId) -> Option<u32> {
+        Some(self.entities.get(&id)?.layers)
+    }
+
+    // Replaces the bounds taken from the render's model, in the entity's local space
+    pub fn set_bounds(&mut self, id: EntityId, bounds: Option<Aabb>) -> Result<(), WorldError> {
+        self.entities
+            .get_mut(&id)
+            .ok_or(WorldError::DeadEntity(id))?
+            .bounds = bounds;
+        Ok(())
+    }
+
+    // Interpolated parents move their children too, so the chain is composed up to the first
+    // ancestor whose cached world matrix is already correct
+    fn render_matrix(&self, entity: &EntityData) -> Matrix4<f32> {
+        if !entity.interpolated_tree {
+            return entity.world;
+        }
+
+        let parent = entity
+            .parent
+            .map(|parent| self.render_matrix(self.entities.get(&parent).unwrap()))
+            .unwrap_or(Matrix4::identity());
+        let local = if entity.interpolate {
+            entity.previous.lerp(&entity.transform, self.alpha)
+        } else {
+            entity.transform
+        };
+
+        parent * local.make_model_matrix()
+    }
+
+    fn render_cameras(&self, renderer: &mut EntityRenderer) {
+        let mut cameras = Vec::from_iter(self.cameras.iter().filter(|(_, camera)| camera.active));
+        cameras.sort_by_key(|(&id, camera)| (camera.priority, id));
+
+        // Matrices and bounding spheres are shared by every camera, only the layer and frustum tests differ
+        let live_renders = self
+            .render_to_entities
+            .iter()
+            .filter(|(&render, _)| renderer.is_alive(render));
+        let mut drawables = Vec::from_iter(live_renders.map(|(&render, ids)| {
+            let render_bounds = renderer.bounds(render);
+            let entities = ids
+                .iter()
+                .map(|id| self.entities.get(id).unwrap())
+                .filter(|entity| entity.visible_tree);
+            let instances = Vec::from_iter(entities.map(|entity| {
+                let matrix = self.render_matrix(entity);
+                let sphere = entity
+                    .bounds
+                    .or(render_bounds)
+                    .map(|bounds| bounds.bounding_sphere(&matrix));
+                (matrix, entity.layers, sphere)
+            }));
+            (render, instances)
+        }));
+        drawables.sort_by_key(|(render, _)| *render);
+
+        let mut cleared = HashSet::new();
+        for (id, camera) in cameras {
+            let (width, height) = match renderer.target_size(camera.target) {
+                Ok(size) => size,
+                Err(err) => {
+                    log::warn!("Skipping camera {id}: {err}");
+                    continue;
+                }
+            };
+            let world = self.render_matrix(self.entities.get(id).unwrap());
+            let mut view = camera.make_view(
+                world.invert().unwrap_or(Matrix4::identity()),
+                camera.aspect(width, height),
+            );
+            view.clear = cleared.insert(camera.target);
+            let frustum = Frustum::from_view_proj(&view.view_proj);
+
+            let tasks = Vec::from_iter(drawables.iter().filter_map(|(render, instances)| {
+                let matrices = Vec::from_iter(
+                    instances
+                        .iter()
+                        .filter(|(_, layers, sphere)| {
+                            layers & camera.layers != 0
+                                && sphere.is_none_or(|(center, radius)| {
+                                    frustum.intersects_sphere(center, radius)
+                                })
+                        })
+                        .map(|(matrix, _, _)| *matrix),
+                );
+
+                (!matrices.is_empty()).then_some(RenderTask(*render, matrices))
+            }));
+
+            if let Err(err) = renderer.render(&view, tasks.as_slice()) {
+                log::error!("Camera {id} failed to render: {err}");
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, behavior: &str, enabled: bool) {
+        let idx = self
+            .schedule
+            .index_of(behavior)
+            .unwrap_or_else(|| panic!("No behavior named '{behavior}'"));
+        self.schedule.enabled[idx] = enabled;
+    }
+
+    pub fn is_enabled(&self, behavior: &str) -> bool {
+        self.schedule
+            .index_of(behavior)
+            .is_some_and(|idx| self.schedule.enabled[idx])
+    }
+
+    pub fn commands(&self) -> Commands<'_> {
+        Commands { world: self }
+    }
+
+    // Commands queued while applying are applied in the same call
+    pub fn apply_commands(&mut self, renderer: &mut EntityRenderer) {
+        loop {
+            let commands = std::mem::take(self.commands.get_mut());
+            if commands.is_empty() {
+                break;
+            }
+
+            for command in commands {
+                if let Err(err) = self.apply_command(command, renderer) {
+                    log::warn!("Skipped world command: {err}");
+                }
+            }
+        }
+    }
+
+    fn apply_command(
+        &mut self,
+        command: Command,
+        renderer: &mut EntityRenderer,
+    ) -> Result<(), WorldError> {
+        match command {
+            Command::Spawn { id, tags, render } => {
+                if let Err(err) = check_render(render, renderer) {
+                    self.ids.get_mut().free(id);
+                    return Err(err);
+                }
+                let tags = Vec::from_iter(tags.iter().map(|tag| tag.as_str()));
+                self.spawn_reserved(id, tags.as_slice(), render);
+            }
+            Command::SpawnWithRender {
+                id,
+                tags,
+                texture,
+                model,
+            } => {
+                let render = match renderer.create_render(texture, model) {
+                    Ok(render) => render,
+                    Err(err) => {
+                        self.ids.get_mut().free(id);
+                        return Err(err);
+                    }
+                };
+                let tags = Vec::from_iter(tags.iter().map(|tag| tag.as_str()));
+                self.spawn_reserved(id, tags.as_slice(), Some(render));
+            }
+            Command::Despawn(id) => self.delete_entity(id)?,
+            Command::AddTag(id, tag) => self.add_tag(id, &tag)?,
+            Command::RemoveTag(id, tag) => self.remove_tag(id, &tag)?,
+            Command::SetParent(id, parent) => self.set_parent(id, parent)?,
+            Command::SetTransform(id, transform) => {
+                *self.transform_by_id(id).ok_or(WorldError::DeadEntity(id))? = transform;
+            }
+            Command::Custom(f) => f(self, renderer)?,
+        }
+        Ok(())
     }
 
     pub fn init(&mut self, resources: &mut EngineResources) {
@@ -166,12 +1025,104 @@ impl World {
 
         for behavior in behaviors.iter_mut() {
             behavior.init(self, &mut renderer);
+            self.apply_commands(&mut renderer);
         }
 
         self.renderer_storage = Some(storage);
         self.behaviors = Some(behaviors);
     }
 
+    pub fn with_renderer<R>(
+        &mut self,
+        resources: &mut EngineResources,
+        f: impl FnOnce(&mut World, &mut EntityRenderer) -> R,
+    ) -> R {
+        let mut storage = self.renderer_storage.take().unwrap();
+
+        let mut renderer = EntityRenderer {
+            storage: &mut storage,
+            resources,
+        };
+
+        let result = f(self, &mut renderer);
+
+        self.renderer_storage = Some(storage);
+        result
+    }
+
+    // Steps beyond the cap are dropped so a long stall can't spiral into ever longer frames
+    fn advance_clock(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt;
+        let mut substeps = 0;
+        while self.accumulator >= self.fixed_dt {
+            if substeps == self.max_substeps {
+                self.accumulator %= self.fixed_dt;
+                break;
+            }
+
+            self.accumulator -= self.fixed_dt;
+            substeps += 1;
+        }
+        self.alpha = self.accumulator / self.fixed_dt;
+        substeps
+    }
+
+    fn fixed_step(
+        &mut self,
+        behaviors: &mut [Box<dyn WorldBehavior>],
+        renderer: &mut EntityRenderer,
+    ) {
+        for entity in self.entities.values_mut() {
+            if entity.interpolate {
+                entity.previous = entity.transform;
+            }
+        }
+
+        for stage in Stage::ALL {
+            for b_idx in self.schedule.stage(stage) {
+                behaviors[b_idx].fixed_update(self, renderer, self.fixed_dt);
+                self.apply_commands(renderer);
+            }
+        }
+
+        self.run_stage(Stage::FixedUpdate, behaviors, renderer, self.fixed_dt);
+    }
+
+    // Exclusive behaviors run on this thread, parallel batches on the rayon pool
+    fn run_stage(
+        &mut self,
+        stage: Stage,
+        behaviors: &mut [Box<dyn WorldBehavior>],
+        renderer: &mut EntityRenderer,
+        dt: f32,
+    ) {
+        for batch in self.schedule.batches(stage) {
+            match batch {
+                Batch::Exclusive(b_idx) => behaviors[b_idx].run(self, renderer, dt),
+                Batch::Parallel(indices) => {
+                    let world = &*self;
+                    let mut batch = Vec::from_iter(
+                        behaviors
+                            .iter_mut()
+                            .enumerate()
+                            .filter(|(b_idx, _)| indices.contains(b_idx)),
+                    );
+
+                    batch.par_iter_mut().for_each(|(b_idx, behavior)| {
+                        let view = WorldView {
+                            world,
+                            access: world.schedule.accesses[*b_idx].as_ref().unwrap(),
+                            behavior: world.schedule.names[*b_idx],
+                        };
+                        behavior.run_parallel(&view, dt);
+                    });
+                }
+            }
+
+            self.apply_commands(renderer);
+        }
+    }
+
     pub fn run(&mut self, dt: f32, resources: &mut EngineResources) {
         let mut behaviors = self.behaviors.take().unwrap();
         let mut storage = self.renderer_storage.take().unwrap();
@@ -214,11 +1165,190 @@ impl World {
                 .on_created(self, &mut renderer, c_indices.as_slice());
         }
 
-        for behavior in behaviors.iter_mut() {
-            behavior.run(self, &mut renderer, dt);
+        self.apply_commands(&mut renderer);
+
+        for _ in 0..self.advance_clock(dt) {
+            self.fixed_step(&mut behaviors, &mut renderer);
         }
 
+        for stage in Stage::ALL {
+            if stage == Stage::FixedUpdate {
+                continue;
+            }
+
+            if stage == Stage::Render {
+                self.update_world_transforms();
+            }
+
+            self.run_stage(stage, &mut behaviors, &mut renderer, dt);
+        }
+
+        self.update_world_transforms();
+        self.render_cameras(&mut renderer);
+        self.update_events();
+
         self.renderer_storage = Some(storage);
         self.behaviors = Some(behaviors);
     }
+
+    // Swapped at the end of a frame, so events sent between frames, like the window events, are
+    // readable for the whole frame that follows and the one after
+    fn update_events(&mut self) {
+        for events in self.events.values_mut() {
+            events.get_mut().update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_relative_eq, vec3};
+
+    use super::*;
+
+    // Entities without a render, so no renderer is needed
+    fn spawn(world: &mut World) -> EntityId {
+        let id = world.ids.get_mut().alloc();
+        world.spawn_reserved(id, &[], None);
+        id
+    }
+
+    fn translation(matrix: Matrix4<f32>) -> Vector3<f32> {
+        matrix.w.truncate()
+    }
+
+    #[test]
+    fn events_sent_between_frames_last_two_frames() {
+        let mut world = World::new(Vec::new()).unwrap();
+        let mut reader = events::EventReader::new();
+        world.send(WindowFocused(true));
+
+        // End of the first frame, a reader that only runs in the second one still sees it
+        world.update_events();
+        assert_eq!(world.events::<WindowFocused>().read(&mut reader).count(), 1);
+
+        world.update_events();
+        assert!(world.events::<WindowFocused>().is_empty());
+    }
+
+    #[test]
+    fn tag_queries_follow_added_and_removed_tags() {
+        let mut world = World::new(Vec::new()).unwrap();
+        let (a, b) = (spawn(&mut world), spawn(&mut world));
+        world.add_tag(a, "enemy").unwrap();
+        world.add_tag(b, "enemy").unwrap();
+        world.add_tag(b, "boss").unwrap();
+
+        let bosses = Vec::from_iter(world.query_tags(tags::all_of(["enemy", "boss"])));
+        assert_eq!(bosses, vec![b]);
+        let minions = Vec::from_iter(world.query_tags(tags::all_of(["enemy"]).none_of(["boss"])));
+        assert_eq!(minions, vec![a]);
+
+        world.remove_tag(b, "boss").unwrap();
+        assert_eq!(world.query_tags(tags::all_of(["boss"])).count(), 0);
+        assert!(world.remove_tag(b, "boss").is_ok());
+        assert_eq!(world.ids_by_tag("enemy").len(), 2);
+    }
+
+    #[test]
+    fn clock_runs_whole_steps_and_keeps_the_remainder() {
+        let mut world = World::new(Vec::new()).unwrap();
+        world.set_fixed_rate(10.0);
+
+        assert_eq!(world.advance_clock(0.25), 2);
+        assert_relative_eq!(world.alpha, 0.5, epsilon = 1e-4);
+        assert_eq!(world.advance_clock(0.05), 1);
+        assert_relative_eq!(world.alpha, 0.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn clock_drops_steps_beyond_the_cap() {
+        let mut world = World::new(Vec::new()).unwrap();
+        world.set_fixed_rate(10.0);
+        world.set_max_substeps(3);
+
+        assert_eq!(world.advance_clock(1.05), 3);
+        assert_relative_eq!(world.alpha, 0.5, epsilon = 1e-3);
+        assert_eq!(world.advance_clock(0.0), 0);
+    }
+
+    #[test]
+    fn interpolated_parent_moves_its_children() {
+        let mut world = World::new(Vec::new()).unwrap();
+        let parent = spawn(&mut world);
+        let child = spawn(&mut world);
+        world.set_parent(child, Some(parent)).unwrap();
+        world.transform_by_id(child).unwrap().pos = vec3(0.0, 1.0, 0.0);
+
+        world.set_interpolated(parent, true).unwrap();
+        world.transform_by_id(parent).unwrap().pos = vec3(10.0, 0.0, 0.0);
+        world.alpha = 0.5;
+        world.update_world_transforms();
+
+        let child_data = world.entities.get(&child).unwrap();
+        assert_relative_eq!(
+            translation(world.render_matrix(child_data)),
+            vec3(5.0, 1.0, 0.0)
+        );
+        assert_relative_eq!(translation(child_data.world), vec3(10.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn set_interpolated_only_syncs_when_enabling() {
+        let mut world = World::new(Vec::new()).unwrap();
+        let id = spawn(&mut world);
+        world.set_interpolated(id, true).unwrap();
+        world.transform_by_id(id).unwrap().pos = vec3(4.0, 0.0, 0.0);
+
+        world.set_interpolated(id, true).unwrap();
+        assert_relative_eq!(
+            world.entities.get(&id).unwrap().previous.pos,
+            vec3(0.0, 0.0, 0.0)
+        );
+
+        world.set_interpolated(id, false).unwrap();
+        world.set_interpolated(id, true).unwrap();
+        assert_relative_eq!(
+            world.entities.get(&id).unwrap().previous.pos,
+            vec3(4.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut world = World::new(Vec::new()).unwrap();
+        let root = spawn(&mut world);
+        let child = spawn(&mut world);
+        let grandchild = spawn(&mut world);
+        world.set_parent(child, Some(root)).unwrap();
+        world.set_parent(grandchild, Some(child)).unwrap();
+
+        assert!(matches!(
+            world.set_parent(root, Some(grandchild)),
+            Err(WorldError::ParentCycle(..))
+        ));
+        assert!(matches!(
+            world.set_parent(root, Some(root)),
+            Err(WorldError::ParentCycle(..))
+        ));
+        assert_eq!(world.parent(root), None);
+        assert_eq!(world.children(root), &[child]);
+    }
+
+    #[test]
+    fn hiding_a_parent_hides_its_subtree() {
+        let mut world = World::new(Vec::new()).unwrap();
+        let parent = spawn(&mut world);
+        let child = spawn(&mut world);
+        world.set_parent(child, Some(parent)).unwrap();
+
+        world.set_visible(parent, false).unwrap();
+        world.update_world_transforms();
+        assert!(!world.entities.get(&child).unwrap().visible_tree);
+        assert!(!world.is_visible(child));
+
+        world.set_visible(parent, true).unwrap();
+        world.update_world_transforms();
+        assert!(world.entities.get(&child).unwrap().visible_tree);
+    }
 }
@@ -0,0 +1,270 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use serde::Serialize;
+
+use super::{
+    entity_renderer::{EntityModel, EntityRenderer},
+    id::{EntityId, RenderId},
+    scene::{self, Scene, SceneError, SceneTexture},
+    EntityTransform, World,
+};
+
+// Prefab files use the scene format with one root listed first, `name` marks entities overrides can target
+pub(super) struct Prefab {
+    scene: Scene,
+    renders: Vec<RenderId>,
+    // Renders made for texture overrides, shared by every instance overriding a node the same way
+    overrides: HashMap<(String, SceneTexture), RenderId>,
+}
+
+impl Prefab {
+    fn load(
+        path: &str,
+        world: &World,
+        renderer: &mut EntityRenderer,
+    ) -> Result<Prefab, SceneError> {
+        let full_path = Path::new(renderer.resources.resource_manager.root).join(path);
+        let scene: Scene = ron::from_str(&fs::read_to_string(full_path)?)?;
+        Self::validate(path, &scene, world)?;
+
+        let renders = scene::create_renders(&scene.renders, renderer, true)?;
+        Ok(Self {
+            scene,
+            renders,
+            overrides: HashMap::new(),
+        })
+    }
+
+    // Runs before any render is made, so a broken prefab leaves nothing behind in the renderer
+    fn validate(path: &str, scene: &Scene, world: &World) -> Result<(), SceneError> {
+        let is_tree = scene
+            .entities
+            .first()
+            .is_some_and(|root| root.parent.is_none())
+            && scene.entities[1..]
+                .iter()
+                .all(|entity| entity.parent.is_some());
+        if !is_tree {
+            return Err(SceneError::NotATree(String::from(path)));
+        }
+        world.check_entities(&scene.entities, scene.renders.len())
+    }
+
+    // Renders deleted since the prefab was cached can't be handed to new instances
+    fn is_alive(&self, renderer: &EntityRenderer) -> bool {
+        self.renders.iter().all(|&render| renderer.is_alive(render))
+    }
+}
+
+#[derive(Default)]
+struct NodeOverride {
+    transform: Option<EntityTransform>,
+    tags: Vec<String>,
+    texture: Option<SceneTexture>,
+    visible: Option<bool>,
+    components: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct PrefabOverrides {
+    nodes: HashMap<String, NodeOverride>,
+    // First component that failed to serialize, reported when spawning
+    error: Option<ron::Error>,
+}
+
+impl PrefabOverrides {
+    pub fn new() -> PrefabOverrides {
+        Self::default()
+    }
+
+    pub fn transform(mut self, node: &str, transform: EntityTransform) -> PrefabOverrides {
+        self.node(node).transform = Some(transform);
+        self
+    }
+
+    pub fn tag(mut self, node: &str, tag: &str) -> PrefabOverrides {
+        self.node(node).tags.push(String::from(tag));
+        self
+    }
+
+    pub fn texture(mut self, node: &str, texture: SceneTexture) -> PrefabOverrides {
+        self.node(node).texture = Some(texture);
+        self
+    }
+
+    pub fn visible(mut self, node: &str, visible: bool) -> PrefabOverrides {
+        self.node(node).visible = Some(visible);
+        self
+    }
+
+    // `name` is the one the component was registered under with `register_scene_component`
+    pub fn component<T: Serialize>(
+        mut self,
+        node: &str,
+        name: &str,
+        component: &T,
+    ) -> PrefabOverrides {
+        match ron::to_string(component) {
+            Ok(text) => {
+                self.node(node).components.insert(String::from(name), text);
+            }
+            Err(err) => {
+                self.error.get_or_insert(err);
+            }
+        }
+        self
+    }
+
+    fn node(&mut self, node: &str) -> &mut NodeOverride {
+        self.nodes.entry(String::from(node)).or_default()
+    }
+}
+
+impl World {
+    pub fn spawn_prefab(
+        &mut self,
+        path: &str,
+        transform: EntityTransform,
+        renderer: &mut EntityRenderer,
+    ) -> Result<EntityId, SceneError> {
+        self.spawn_prefab_with(path, transform, &PrefabOverrides::new(), renderer)
+    }
+
+    // Instances share the renders made for the first one, an overridden texture gets its own render
+    // aliasing the mesh, made once per node and texture and reused by later instances
+    pub fn spawn_prefab_with(
+        &mut self,
+        path: &str,
+        transform: EntityTransform,
+        overrides: &PrefabOverrides,
+        renderer: &mut EntityRenderer,
+    ) -> Result<EntityId, SceneError> {
+        if let Some(err) = &overrides.error {
+            return Err(SceneError::Serialize(err.clone()));
+        }
+
+        let cached = self
+            .prefabs
+            .get(path)
+            .is_some_and(|prefab| prefab.is_alive(renderer));
+        if !cached {
+            let prefab = Prefab::load(path, self, renderer)?;
+            self.prefabs.insert(String::from(path), prefab);
+        }
+
+        let prefab = self.prefabs.get_mut(path).unwrap();
+        let mut entities = prefab.scene.entities.clone();
+        let mut renders = prefab.renders.clone();
+
+        for (node, node_override) in overrides.nodes.iter() {
+            let entity = entities
+                .iter_mut()
+                .find(|entity| entity.name.as_deref() == Some(node))
+                .ok_or_else(|| SceneError::UnknownNode(node.clone()))?;
+
+            if let Some(transform) = node_override.transform {
+                entity.transform = transform.into();
+            }
+            if let Some(visible) = node_override.visible {
+                entity.visible = visible;
+            }
+            for tag in node_override.tags.iter() {
+                if !entity.tags.contains(tag) {
+                    entity.tags.push(tag.clone());
+                }
+            }
+            entity.components.extend(
+                node_override
+                    .components
+                    .iter()
+                    .map(|(name, text)| (name.clone(), text.clone())),
+            );
+
+            if let Some(texture) = &node_override.texture {
+                let key = (node.clone(), texture.clone());
+                let render = match prefab.overrides.get(&key) {
+                    Some(&render) if renderer.is_alive(render) => render,
+                    _ => {
+                        let model = entity
+                            .render
+                            .and_then(|idx| renders.get(idx))
+                            .and_then(|&render| shared_model(renderer, render));
+                        let render =
+                            renderer.create_render(Some(scene::create_texture(texture)), model)?;
+                        prefab.overrides.insert(key, render);
+                        render
+                    }
+                };
+                renders.push(render);
+                entity.render = Some(renders.len() - 1);
+            }
+        }
+
+        let root = &mut entities[0];
+        root.transform = transform.compose(&root.transform.into()).into();
+
+        Ok(self.spawn_entities(&entities, &renders, renderer)?[0])
+    }
+}
+
+// Model for a new render that draws the same mesh as `render`
+fn shared_model(renderer: &EntityRenderer, render: RenderId) -> Option<EntityModel> {
+    match renderer.describe(render)? {
+        (_, Some(&EntityModel::Alias(owner))) => Some(EntityModel::Alias(owner)),
+        (_, Some(_)) => Some(EntityModel::Alias(render)),
+        (_, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{ser::Error, Serializer};
+
+    use super::*;
+
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("cannot serialize"))
+        }
+    }
+
+    #[test]
+    fn component_keeps_the_first_serialize_error() {
+        let overrides = PrefabOverrides::new()
+            .component("root", "health", &10)
+            .component("root", "broken", &Unserializable)
+            .component("root", "armor", &5);
+
+        assert!(overrides.error.is_some());
+        let components = &overrides.nodes["root"].components;
+        assert_eq!(components.get("health").map(String::as_str), Some("10"));
+        assert!(!components.contains_key("broken"));
+    }
+
+    #[test]
+    fn validate_rejects_broken_prefabs() {
+        let world = World::new(Vec::new()).unwrap();
+        let scene = |text: &str| ron::from_str::<Scene>(text).unwrap();
+
+        let two_roots = scene("(renders: [], entities: [(), ()])");
+        assert!(matches!(
+            Prefab::validate("two_roots", &two_roots, &world),
+            Err(SceneError::NotATree(_))
+        ));
+
+        let missing_render = scene("(renders: [], entities: [(render: Some(0))])");
+        assert!(matches!(
+            Prefab::validate("missing_render", &missing_render, &world),
+            Err(SceneError::InvalidRender(0))
+        ));
+
+        let tree = scene("(renders: [], entities: [(), (parent: Some(0))])");
+        assert!(Prefab::validate("tree", &tree, &world).is_ok());
+    }
+}
@@ -0,0 +1,667 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::Path,
+    sync::Mutex,
+};
+
+use cgmath::{Quaternion, Vector3};
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    check_render,
+    component::Component,
+    entity_renderer::{CubemapSource, EntityModel, EntityRenderer, EntityTexture},
+    id::{EntityId, RenderId},
+    EntityTransform, World, WorldError, DEFAULT_LAYERS,
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Parse(ron::error::SpannedError),
+    UnknownComponent(String),
+    InvalidRender(usize),
+    InvalidParent(usize),
+    NotATree(String),
+    UnknownNode(String),
+    UnsavableRender(RenderId),
+    World(WorldError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "Scene file error: {err}"),
+            SceneError::Serialize(err) => write!(f, "Cannot serialize scene: {err}"),
+            SceneError::Parse(err) => write!(f, "Cannot parse scene: {err}"),
+            SceneError::UnknownComponent(name) => {
+                write!(f, "Component '{name}' was never registered for scenes")
+            }
+            SceneError::InvalidRender(idx) => write!(f, "Scene has no render at index {idx}"),
+            SceneError::InvalidParent(idx) => {
+                write!(
+                    f,
+                    "Scene entity parent {idx} does not come before its children"
+                )
+            }
+            SceneError::NotATree(path) => {
+                write!(
+                    f,
+                    "Prefab '{path}' must have a single root entity listed first"
+                )
+            }
+            SceneError::UnknownNode(name) => write!(f, "Prefab has no entity named '{name}'"),
+            SceneError::UnsavableRender(id) => {
+                write!(
+                    f,
+                    "Render {id} has a texture that can't be saved to a scene"
+                )
+            }
+            SceneError::World(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+impl From<ron::Error> for SceneError {
+    fn from(err: ron::Error) -> Self {
+        SceneError::Serialize(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SceneError::Parse(err)
+    }
+}
+
+impl From<WorldError> for SceneError {
+    fn from(err: WorldError) -> Self {
+        SceneError::World(err)
+    }
+}
+
+// Entities are stored parents first, and refer to parents and renders by their index in the scene
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Scene {
+    pub renders: Vec<SceneRender>,
+    pub entities: Vec<SceneEntity>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SceneRender {
+    pub texture: Option<SceneTexture>,
+    pub model: Option<SceneModel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SceneTexture {
+    Resource(Vec<String>),
+    AtlasedResource(Vec<String>),
+    ResourceArray(Vec<String>),
+    ResourceCube(SceneCubemap),
+    ResourceVolume(String, u32),
+}
+
+// Faces are in +X, -X, +Y, -Y, +Z, -Z order
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SceneCubemap {
+    Faces([String; 6]),
+    Cross(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum SceneModel {
+    Resource(String),
+    InitialSize(usize),
+    Alias(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SceneEntity {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub parent: Option<usize>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub transform: SceneTransform,
+    #[serde(default)]
+    pub render: Option<usize>,
+    #[serde(default = "visible_default")]
+    pub visible: bool,
+    #[serde(default = "layers_default")]
+    pub layers: u32,
+    // Each component is kept as its own RON string so enum and struct names survive the round trip
+    #[serde(default)]
+    pub components: BTreeMap<String, String>,
+}
+
+fn visible_default() -> bool {
+    true
+}
+
+fn layers_default() -> u32 {
+    DEFAULT_LAYERS
+}
+
+// Rotation is stored as [x, y, z, w]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct SceneTransform {
+    pub pos: [f32; 3],
+    pub rot: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl Default for SceneTransform {
+    fn default() -> Self {
+        EntityTransform::new().into()
+    }
+}
+
+impl From<EntityTransform> for SceneTransform {
+    fn from(transform: EntityTransform) -> Self {
+        let rot = transform.rot;
+        Self {
+            pos: transform.pos.into(),
+            rot: [rot.v.x, rot.v.y, rot.v.z, rot.s],
+            scale: transform.scale.into(),
+        }
+    }
+}
+
+impl From<SceneTransform> for EntityTransform {
+    fn from(transform: SceneTransform) -> Self {
+        let [x, y, z, w] = transform.rot;
+        Self {
+            pos: Vector3::from(transform.pos),
+            rot: Quaternion::new(w, x, y, z),
+            scale: Vector3::from(transform.scale),
+        }
+    }
+}
+
+pub(super) struct SceneComponent {
+    name: &'static str,
+    save: fn(&World, EntityId) -> Option<Result<String, ron::Error>>,
+    load: fn(&mut World, EntityId, &str) -> Result<(), SceneError>,
+}
+
+impl SceneComponent {
+    fn new<T: Component + Serialize + DeserializeOwned>(name: &'static str) -> SceneComponent {
+        Self {
+            name,
+            save: |world, id| {
+                world
+                    .get::<T>(id)
+                    .map(|component| ron::to_string(&*component))
+            },
+            load: |world, id, text| {
+                world.insert(id, ron::from_str::<T>(text)?)?;
+                Ok(())
+            },
+        }
+    }
+}
+
+// Render descriptions hold `&'static str` paths, so each loaded path is leaked once and reused
+static PATHS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn intern(path: &str) -> &'static str {
+    let mut paths = PATHS.lock().unwrap();
+    if let Some(&interned) = paths.iter().find(|&&interned| interned == path) {
+        return interned;
+    }
+
+    let interned = Box::leak(String::from(path).into_boxed_str());
+    paths.push(interned);
+    interned
+}
+
+fn intern_all(paths: &[String]) -> Vec<&'static str> {
+    Vec::from_iter(paths.iter().map(|path| intern(path)))
+}
+
+fn to_strings(paths: &[&'static str]) -> Vec<String> {
+    Vec::from_iter(paths.iter().map(|&path| String::from(path)))
+}
+
+impl World {
+    pub fn register_scene_component<T: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) {
+        if self.scene_components.iter().any(|c| c.name == name) {
+            panic!("Scene component '{name}' is already registered");
+        }
+        self.scene_components.push(SceneComponent::new::<T>(name));
+    }
+
+    // Path is relative to the resource root
+    pub fn save_scene(&self, path: &str, renderer: &EntityRenderer) -> Result<(), SceneError> {
+        let scene = self.to_scene(renderer)?;
+        let text = ron::ser::to_string_pretty(&scene, PrettyConfig::default())?;
+
+        let full_path = Path::new(renderer.resources.resource_manager.root).join(path);
+        if let Some(dir) = full_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(full_path, text)?;
+        Ok(())
+    }
+
+    // Adds the scene's entities next to whatever is already in the world
+    pub fn load_scene(
+        &mut self,
+        path: &str,
+        renderer: &mut EntityRenderer,
+    ) -> Result<Vec<EntityId>, SceneError> {
+        let full_path = Path::new(renderer.resources.resource_manager.root).join(path);
+        let scene: Scene = ron::from_str(&fs::read_to_string(full_path)?)?;
+        self.spawn_scene(&scene, renderer)
+    }
+
+    pub fn to_scene(&self, renderer: &EntityRenderer) -> Result<Scene, SceneError> {
+        let mut stack = Vec::from_iter(
+            self.entities
+                .iter()
+                .filter(|(_, entity)| entity.parent.is_none())
+                .map(|(&id, _)| id),
+        );
+        stack.sort_by(|a, b| b.cmp(a));
+
+        let mut order = Vec::new();
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.entities[&id].children.iter().rev());
+        }
+
+        let scene_index: HashMap<EntityId, usize> =
+            HashMap::from_iter(order.iter().enumerate().map(|(idx, &id)| (id, idx)));
+
+        let mut scene = Scene::default();
+        let mut saved_renders = HashMap::new();
+
+        for &id in order.iter() {
+            let entity = &self.entities[&id];
+            let render = match entity.render {
+                Some(render) => save_render(renderer, render, &mut scene, &mut saved_renders)?,
+                None => None,
+            };
+
+            let mut components = BTreeMap::new();
+            for component in self.scene_components.iter() {
+                if let Some(text) = (component.save)(self, id) {
+                    components.insert(String::from(component.name), text?);
+                }
+            }
+
+            scene.entities.push(SceneEntity {
+                name: None,
+                parent: entity.parent.map(|parent| scene_index[&parent]),
+                tags: self.entity_to_tags[&id].clone(),
+                transform: entity.transform.into(),
+                render,
+                visible: entity.visible,
+                layers: entity.layers,
+                components,
+            });
+        }
+
+        Ok(scene)
+    }
+
+    pub fn spawn_scene(
+        &mut self,
+        scene: &Scene,
+        renderer: &mut EntityRenderer,
+    ) -> Result<Vec<EntityId>, SceneError> {
+        self.check_entities(&scene.entities, scene.renders.len())?;
+
+        let renders = create_renders(&scene.renders, renderer, false)?;
+        self.spawn_entities(&scene.entities, &renders, renderer)
+            .inspect_err(|_| delete_renders(&renders, renderer))
+    }
+
+    pub(super) fn spawn_entities(
+        &mut self,
+        entities: &[SceneEntity],
+        renders: &[RenderId],
+        renderer: &EntityRenderer,
+    ) -> Result<Vec<EntityId>, SceneError> {
+        self.check_entities(entities, renders.len())?;
+        for entity in entities.iter() {
+            check_render(entity.render.map(|idx| renders[idx]), renderer)?;
+        }
+        self.spawn_checked(entities, renders)
+    }
+
+    fn spawn_checked(
+        &mut self,
+        entities: &[SceneEntity],
+        renders: &[RenderId],
+    ) -> Result<Vec<EntityId>, SceneError> {
+        let mut ids = Vec::new();
+        if let Err(err) = self.spawn_each(entities, renders, &mut ids) {
+            // Only a component failing to parse gets here, so the scene is spawned entirely or not at all
+            for &id in ids.iter().rev() {
+                if self.is_alive(id) {
+                    self.delete_entity(id)?;
+                }
+            }
+            return Err(err);
+        }
+        Ok(ids)
+    }
+
+    // Everything that can be checked without spawning, so a bad scene leaves the world untouched
+    pub(super) fn check_entities(
+        &self,
+        entities: &[SceneEntity],
+        renders: usize,
+    ) -> Result<(), SceneError> {
+        for (idx, entity) in entities.iter().enumerate() {
+            match entity.render {
+                Some(render) if render >= renders => return Err(SceneError::InvalidRender(render)),
+                _ => (),
+            }
+            match entity.parent {
+                Some(parent) if parent >= idx => return Err(SceneError::InvalidParent(parent)),
+                _ => (),
+            }
+            for name in entity.components.keys() {
+                if !self.scene_components.iter().any(|c| c.name == name) {
+                    return Err(SceneError::UnknownComponent(name.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn_each(
+        &mut self,
+        entities: &[SceneEntity],
+        renders: &[RenderId],
+        ids: &mut Vec<EntityId>,
+    ) -> Result<(), SceneError> {
+        for entity in entities.iter() {
+            let tags = Vec::from_iter(entity.tags.iter().map(String::as_str));
+            let id = self.ids.get_mut().alloc();
+            self.spawn_reserved(id, &tags, entity.render.map(|idx| renders[idx]));
+            ids.push(id);
+
+            if let Some(parent) = entity.parent {
+                self.set_parent(id, Some(ids[parent]))?;
+            }
+
+            let data = self.entities.get_mut(&id).unwrap();
+            data.transform = entity.transform.into();
+            data.previous = data.transform;
+            data.visible = entity.visible;
+            data.layers = entity.layers;
+
+            for (name, text) in entity.components.iter() {
+                let component = self.scene_components.iter().find(|c| c.name == name);
+                (component.unwrap().load)(self, id, text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn create_texture(texture: &SceneTexture) -> EntityTexture {
+    match texture {
+        SceneTexture::Resource(paths) => EntityTexture::Resource(intern_all(paths)),
+        SceneTexture::AtlasedResource(paths) => EntityTexture::AtlasedResource(intern_all(paths)),
+        SceneTexture::ResourceArray(paths) => EntityTexture::ResourceArray(intern_all(paths)),
+        SceneTexture::ResourceCube(SceneCubemap::Faces(paths)) => {
+            EntityTexture::ResourceCube(CubemapSource::Faces(paths.each_ref().map(|p| intern(p))))
+        }
+        SceneTexture::ResourceCube(SceneCubemap::Cross(path)) => {
+            EntityTexture::ResourceCube(CubemapSource::Cross(intern(path)))
+        }
+        SceneTexture::ResourceVolume(path, depth) => {
+            EntityTexture::ResourceVolume(intern(path), *depth)
+        }
+    }
+}
+
+// With `share_meshes`, renders loading the same model alias the first one's mesh instead of
+// uploading it again, so editing the vertices of one changes all of them. Only prefabs share,
+// their renders are already shared by every instance
+pub(super) fn create_renders(
+    renders: &[SceneRender],
+    renderer: &mut EntityRenderer,
+    share_meshes: bool,
+) -> Result<Vec<RenderId>, SceneError> {
+    for (idx, render) in renders.iter().enumerate() {
+        match render.model {
+            Some(SceneModel::Alias(target)) if target >= idx => {
+                return Err(SceneError::InvalidRender(target))
+            }
+            _ => (),
+        }
+    }
+
+    let mut ids: Vec<RenderId> = Vec::new();
+    let mut model_owners: HashMap<&str, RenderId> = HashMap::new();
+
+    for render in renders.iter() {
+        let texture = render.texture.as_ref().map(create_texture);
+        let model = match render.model {
+            Some(SceneModel::Resource(ref path)) => match model_owners.get(path.as_str()) {
+                Some(&owner) => Some(EntityModel::Alias(owner)),
+                None => Some(EntityModel::Resource(intern(path))),
+            },
+            Some(SceneModel::InitialSize(size)) => Some(EntityModel::InitialSize(size)),
+            Some(SceneModel::Alias(idx)) => Some(EntityModel::Alias(ids[idx])),
+            None => None,
+        };
+
+        let id = match renderer.create_render(texture, model) {
+            Ok(id) => id,
+            Err(err) => {
+                delete_renders(&ids, renderer);
+                return Err(err.into());
+            }
+        };
+        match render.model {
+            Some(SceneModel::Resource(ref path)) if share_meshes => {
+                model_owners.entry(path).or_insert(id);
+            }
+            _ => (),
+        }
+        ids.push(id);
+    }
+
+    Ok(ids)
+}
+
+// Undoes `create_renders`, aliases come after their owners so they go first
+pub(super) fn delete_renders(ids: &[RenderId], renderer: &mut EntityRenderer) {
+    for &id in ids.iter().rev() {
+        renderer.delete_render(id).unwrap();
+    }
+}
+
+// Renders whose textures weren't loaded from resource paths can't be saved and fail the whole scene
+fn save_render(
+    renderer: &EntityRenderer,
+    id: RenderId,
+    scene: &mut Scene,
+    saved: &mut HashMap<RenderId, Option<usize>>,
+) -> Result<Option<usize>, SceneError> {
+    if let Some(&idx) = saved.get(&id) {
+        return Ok(idx);
+    }
+    saved.insert(id, None);
+
+    let (texture, model) = match renderer.describe(id) {
+        Some(description) => description,
+        None => return Ok(None),
+    };
+    let texture = match texture {
+        Some(EntityTexture::Resource(paths)) => Some(SceneTexture::Resource(to_strings(paths))),
+        Some(EntityTexture::AtlasedResource(paths)) => {
+            Some(SceneTexture::AtlasedResource(to_strings(paths)))
+        }
+        Some(EntityTexture::ResourceArray(paths)) => {
+            Some(SceneTexture::ResourceArray(to_strings(paths)))
+        }
+        Some(EntityTexture::ResourceCube(CubemapSource::Faces(paths))) => Some(
+            SceneTexture::ResourceCube(SceneCubemap::Faces(paths.map(String::from))),
+        ),
+        Some(EntityTexture::ResourceCube(CubemapSource::Cross(path))) => Some(
+            SceneTexture::ResourceCube(SceneCubemap::Cross(String::from(*path))),
+        ),
+        Some(EntityTexture::ResourceVolume(path, depth)) => {
+            Some(SceneTexture::ResourceVolume(String::from(*path), *depth))
+        }
+        Some(_) => return Err(SceneError::UnsavableRender(id)),
+        None => None,
+    };
+
+    let model = match model {
+        Some(EntityModel::Resource(path)) => Some(SceneModel::Resource(String::from(*path))),
+        Some(EntityModel::InitialSize(size)) => Some(SceneModel::InitialSize(*size)),
+        Some(EntityModel::Alias(target)) => {
+            save_render(renderer, *target, scene, saved)?.map(SceneModel::Alias)
+        }
+        None => None,
+    };
+
+    scene.renders.push(SceneRender { texture, model });
+    let idx = scene.renders.len() - 1;
+    saved.insert(id, Some(idx));
+    Ok(Some(idx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(parent: Option<usize>) -> SceneEntity {
+        SceneEntity {
+            name: None,
+            parent,
+            tags: Vec::new(),
+            transform: SceneTransform::default(),
+            render: None,
+            visible: true,
+            layers: DEFAULT_LAYERS,
+            components: BTreeMap::new(),
+        }
+    }
+
+    fn scene() -> Scene {
+        let mut root = entity(None);
+        root.tags.push(String::from("root"));
+        root.components
+            .insert(String::from("health"), ron::to_string(&7u32).unwrap());
+
+        let mut child = entity(Some(0));
+        child.transform.pos = [1.0, 2.0, 3.0];
+        child.visible = false;
+        child.layers = 0b10;
+
+        Scene {
+            renders: vec![
+                SceneRender {
+                    texture: Some(SceneTexture::Resource(vec![String::from("tex.png")])),
+                    model: Some(SceneModel::Resource(String::from("model.obj"))),
+                },
+                SceneRender {
+                    texture: Some(SceneTexture::ResourceCube(SceneCubemap::Cross(
+                        String::from("sky.png"),
+                    ))),
+                    model: Some(SceneModel::Alias(0)),
+                },
+                SceneRender {
+                    texture: Some(SceneTexture::ResourceVolume(String::from("fog.png"), 4)),
+                    model: None,
+                },
+            ],
+            entities: vec![root, child],
+        }
+    }
+
+    #[test]
+    fn scenes_survive_a_ron_round_trip() {
+        let scene = scene();
+        let text = ron::ser::to_string_pretty(&scene, PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<Scene>(&text).unwrap(), scene);
+    }
+
+    #[test]
+    fn spawned_scenes_keep_hierarchy_transforms_and_components() {
+        let mut world = World::new(Vec::new()).unwrap();
+        world.register_scene_component::<u32>("health");
+
+        let scene = scene();
+        let ids = world.spawn_checked(&scene.entities, &[]).unwrap();
+
+        assert_eq!(world.parent(ids[1]), Some(ids[0]));
+        assert!(world.has_tag(ids[0], "root"));
+        assert_eq!(world.get::<u32>(ids[0]).map(|health| *health), Some(7));
+        assert_eq!(
+            world.transform(ids[1]).unwrap().pos,
+            Vector3::new(1.0, 2.0, 3.0)
+        );
+        assert!(!world.is_visible(ids[1]));
+        assert_eq!(world.layers(ids[1]), Some(0b10));
+    }
+
+    #[test]
+    fn entities_are_checked_before_spawning() {
+        let mut world = World::new(Vec::new()).unwrap();
+        world.register_scene_component::<u32>("health");
+
+        let forward_parent = [entity(Some(1)), entity(None)];
+        assert!(matches!(
+            world.check_entities(&forward_parent, 0),
+            Err(SceneError::InvalidParent(1))
+        ));
+
+        let mut missing_render = entity(None);
+        missing_render.render = Some(0);
+        assert!(matches!(
+            world.check_entities(&[missing_render], 0),
+            Err(SceneError::InvalidRender(0))
+        ));
+
+        let mut unknown = entity(None);
+        unknown
+            .components
+            .insert(String::from("armor"), String::from("1"));
+        assert!(matches!(
+            world.check_entities(&[unknown], 0),
+            Err(SceneError::UnknownComponent(_))
+        ));
+    }
+
+    #[test]
+    fn failed_components_undo_the_whole_scene() {
+        let mut world = World::new(Vec::new()).unwrap();
+        world.register_scene_component::<u32>("health");
+
+        let mut scene = scene();
+        scene.entities[1]
+            .components
+            .insert(String::from("health"), String::from("\"broken\""));
+
+        assert!(world.spawn_checked(&scene.entities, &[]).is_err());
+        assert!(world.ids_by_tag("root").is_empty());
+        assert!(world.entities.is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use super::{access::Access, WorldBehavior, WorldError};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum Stage {
+    PreUpdate,
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    Render,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::Render,
+    ];
+}
+
+pub enum Batch {
+    Exclusive(usize),
+    Parallel(Vec<usize>),
+}
+
+pub struct Schedule {
+    pub names: Vec<&'static str>,
+    pub stages: HashMap<Stage, Vec<usize>>,
+    pub enabled: Vec<bool>,
+    pub accesses: Vec<Option<Access>>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl Schedule {
+    // Orders behaviors by stage, then by their before/after constraints, then by declaration order
+    pub fn new(behaviors: &[Box<dyn WorldBehavior>]) -> Result<Schedule, WorldError> {
+        let names = Vec::from_iter(behaviors.iter().map(|behavior| behavior.name()));
+        for (idx, name) in names.iter().enumerate() {
+            if names[..idx].contains(name) {
+                return Err(WorldError::DuplicateBehavior(name));
+            }
+        }
+
+        let index_of = |name: &str| {
+            names
+                .iter()
+                .position(|&n| n == name)
+                .unwrap_or_else(|| panic!("No behavior named '{name}' to order against"))
+        };
+
+        let mut edges = vec![Vec::new(); behaviors.len()];
+        for (idx, behavior) in behaviors.iter().enumerate() {
+            for &name in behavior.before() {
+                edges[idx].push(index_of(name));
+            }
+            for &name in behavior.after() {
+                edges[index_of(name)].push(idx);
+            }
+        }
+
+        for (from, to_list) in edges.iter().enumerate() {
+            for &to in to_list {
+                if behaviors[from].stage() > behaviors[to].stage() {
+                    panic!(
+                        "Behavior '{}' must run before '{}' but is in a later stage",
+                        names[from], names[to]
+                    );
+                }
+            }
+        }
+
+        let mut in_degree = vec![0; behaviors.len()];
+        for &to in edges.iter().flatten() {
+            in_degree[to] += 1;
+        }
+
+        let mut ready = BinaryHeap::from_iter(
+            (0..behaviors.len())
+                .filter(|&idx| in_degree[idx] == 0)
+                .map(|idx| Reverse((behaviors[idx].stage(), idx))),
+        );
+        let mut stages: HashMap<Stage, Vec<usize>> = HashMap::new();
+        let mut ordered = 0;
+
+        while let Some(Reverse((stage, idx))) = ready.pop() {
+            stages.entry(stage).or_default().push(idx);
+            ordered += 1;
+
+            for &to in edges[idx].iter() {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.push(Reverse((behaviors[to].stage(), to)));
+                }
+            }
+        }
+
+        if ordered != behaviors.len() {
+            panic!("Behavior ordering constraints form a cycle!");
+        }
+
+        Ok(Self {
+            names,
+            enabled: vec![true; behaviors.len()],
+            accesses: Vec::from_iter(behaviors.iter().map(|behavior| behavior.access())),
+            stages,
+            edges,
+        })
+    }
+
+    // Consecutive behaviors share a batch while none of them conflict or are ordered against each other
+    pub fn batches(&self, stage: Stage) -> Vec<Batch> {
+        let mut batches = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+
+        for idx in self.stage(stage) {
+            let Some(access) = &self.accesses[idx] else {
+                if !current.is_empty() {
+                    batches.push(Batch::Parallel(std::mem::take(&mut current)));
+                }
+                batches.push(Batch::Exclusive(idx));
+                continue;
+            };
+
+            let fits = current.iter().all(|&other| {
+                !self.edges[other].contains(&idx)
+                    && !self.edges[idx].contains(&other)
+                    && !access.conflicts(self.accesses[other].as_ref().unwrap())
+            });
+
+            if !fits {
+                batches.push(Batch::Parallel(std::mem::take(&mut current)));
+            }
+            current.push(idx);
+        }
+
+        if !current.is_empty() {
+            batches.push(Batch::Parallel(current));
+        }
+
+        batches
+    }
+
+    pub fn stage(&self, stage: Stage) -> Vec<usize> {
+        Vec::from_iter(
+            self.stages
+                .get(&stage)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|&idx| self.enabled[idx]),
+        )
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|&n| n == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::world::{entity_renderer::EntityRenderer, id::EntityId, World};
+
+    struct Named {
+        name: &'static str,
+        stage: Stage,
+        after: Vec<&'static str>,
+    }
+
+    impl WorldBehavior for Named {
+        fn tags(&mut self) -> &[&'static str] {
+            &[]
+        }
+        fn init(&mut self, _world: &mut World, _renderer: &mut EntityRenderer) {}
+        fn on_created(&mut self, _: &mut World, _: &mut EntityRenderer, _ids: &[EntityId]) {}
+        fn on_deleted(&mut self, _: &mut World, _: &mut EntityRenderer, _ids: &[EntityId]) {}
+        fn run(&mut self, _world: &mut World, _renderer: &mut EntityRenderer, _dt: f32) {}
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn stage(&self) -> Stage {
+            self.stage
+        }
+
+        fn after(&self) -> &[&'static str] {
+            &self.after
+        }
+    }
+
+    fn behavior(
+        name: &'static str,
+        stage: Stage,
+        after: &[&'static str],
+    ) -> Box<dyn WorldBehavior> {
+        Box::new(Named {
+            name,
+            stage,
+            after: after.to_vec(),
+        })
+    }
+
+    #[test]
+    fn orders_by_stage_then_constraints_then_declaration() {
+        let schedule = Schedule::new(&[
+            behavior("draw", Stage::Render, &[]),
+            behavior("late", Stage::Update, &["early"]),
+            behavior("early", Stage::Update, &[]),
+            behavior("other", Stage::Update, &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(schedule.stage(Stage::Update), vec![2, 1, 3]);
+        assert_eq!(schedule.stage(Stage::Render), vec![0]);
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let result = Schedule::new(&[
+            behavior("mover", Stage::Update, &[]),
+            behavior("mover", Stage::PostUpdate, &[]),
+        ]);
+        assert!(matches!(
+            result,
+            Err(WorldError::DuplicateBehavior("mover"))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle")]
+    fn rejects_cycles() {
+        let _ = Schedule::new(&[
+            behavior("a", Stage::Update, &["b"]),
+            behavior("b", Stage::Update, &["a"]),
+        ]);
+    }
+}
@@ -0,0 +1,64 @@
+#[derive(Clone, Default)]
+pub struct TagQuery {
+    pub all: Vec<String>,
+    pub any: Vec<String>,
+    pub none: Vec<String>,
+}
+
+pub fn all_of<'a>(tags: impl IntoIterator<Item = &'a str>) -> TagQuery {
+    TagQuery::default().all_of(tags)
+}
+
+pub fn any_of<'a>(tags: impl IntoIterator<Item = &'a str>) -> TagQuery {
+    TagQuery::default().any_of(tags)
+}
+
+impl TagQuery {
+    pub fn all_of<'a>(mut self, tags: impl IntoIterator<Item = &'a str>) -> TagQuery {
+        self.all.extend(tags.into_iter().map(String::from));
+        self
+    }
+
+    pub fn any_of<'a>(mut self, tags: impl IntoIterator<Item = &'a str>) -> TagQuery {
+        self.any.extend(tags.into_iter().map(String::from));
+        self
+    }
+
+    pub fn none_of<'a>(mut self, tags: impl IntoIterator<Item = &'a str>) -> TagQuery {
+        self.none.extend(tags.into_iter().map(String::from));
+        self
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        self.all.iter().all(|tag| tags.contains(tag))
+            && (self.any.is_empty() || self.any.iter().any(|tag| tags.contains(tag)))
+            && !self.none.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        Vec::from_iter(tags.iter().copied().map(String::from))
+    }
+
+    #[test]
+    fn matches_combines_all_any_and_none() {
+        let query = all_of(["enemy"])
+            .any_of(["flying", "swimming"])
+            .none_of(["dead"]);
+
+        assert!(query.matches(&tags(&["enemy", "flying"])));
+        assert!(!query.matches(&tags(&["enemy"])));
+        assert!(!query.matches(&tags(&["flying", "swimming"])));
+        assert!(!query.matches(&tags(&["enemy", "swimming", "dead"])));
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert!(TagQuery::default().matches(&[]));
+        assert!(any_of([]).matches(&tags(&["anything"])));
+    }
+}
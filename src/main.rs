@@ -6,6 +6,9 @@ fn main() {
     pollster::block_on(async {
         let behaviors = vec![];
 
-        engine::start(World::new(behaviors)).await;
+        match World::new(behaviors) {
+            Ok(world) => engine::start(world).await,
+            Err(err) => eprintln!("Cannot create the world: {err}"),
+        }
     });
 }